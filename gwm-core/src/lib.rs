@@ -36,6 +36,7 @@ extern crate getopts;
 extern crate libc;
 #[macro_use]
 extern crate log;
+extern crate toml;
 extern crate xcb;
 
 pub mod wm;
@@ -42,15 +42,20 @@ extern crate xcb;
 
 use getopts::Options;
 
-use std::env::{args, home_dir, remove_var};
+use std::env::{args, home_dir, remove_var, var_os};
+use std::ffi::OsString;
 use std::ffi::CString;
 use std::fs::{File, OpenOptions};
 use std::os::unix::fs::FileTypeExt;
 use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::ptr::null_mut;
+use std::sync::atomic::Ordering;
 
-use gabelstaplerwm::wm::core::WmCore;
+use gabelstaplerwm::wm::config::Config;
+use gabelstaplerwm::wm::core::{LoopExit, WmCore, TERMINATE};
 use gabelstaplerwm::wm::err::WmError;
 
 use xcb::base::*;
@@ -60,6 +65,11 @@ extern "C" fn sigchld_action(_: libc::c_int) {
     while unsafe { libc::waitpid(-1, null_mut(), libc::WNOHANG) } > 0 { }
 }
 
+/// Ask the main loop to save the state and exit.
+extern "C" fn sigterm_action(_: libc::c_int) {
+    TERMINATE.store(true, Ordering::SeqCst);
+}
+
 /// Initialize the logger and unset the `RUST_LOG` environment variable afterwards.
 fn setup_logger() {
     // fine to unwrap, as this is the only time we call `init`.
@@ -70,10 +80,18 @@ fn setup_logger() {
     remove_var("RUST_LOG");
 }
 
-/// Set up signal handling for `SIGCHLD`.
+/// Set up signal handling for `SIGCHLD` and `SIGTERM`.
 fn setup_sigaction() {
     // we're a good parent - we wait for our children when they get a screaming
     // fit at the checkout lane
+    setup_handler(libc::SIGCHLD, sigchld_action);
+
+    // `poll(3)` is never restarted, so the main loop notices when it is asked to exit
+    setup_handler(libc::SIGTERM, sigterm_action);
+}
+
+/// Set up a handler for a signal.
+fn setup_handler(signal: libc::c_int, handler: extern "C" fn(libc::c_int)) {
     unsafe {
         use std::mem;
 
@@ -81,16 +99,15 @@ fn setup_sigaction() {
         let mut act = mem::uninitialized::<libc::sigaction>();
 
         // convert our handler to a C-style function pointer
-        let f_ptr: *const libc::c_void =
-            mem::transmute(sigchld_action as extern "C" fn(libc::c_int));
+        let f_ptr: *const libc::c_void = mem::transmute(handler);
         act.sa_sigaction = f_ptr as libc::sighandler_t;
 
         // some default values noone cares about
         libc::sigemptyset(&mut act.sa_mask);
         act.sa_flags = libc::SA_RESTART;
 
-        // setup our SIGCHLD-handler
-        if libc::sigaction(libc::SIGCHLD, &act, null_mut()) == -1 {
+        // setup our handler
+        if libc::sigaction(signal, &act, null_mut()) == -1 {
             // crash and burn on failure
             WmError::CouldNotEstablishSignalHandlers.handle();
         }
@@ -135,13 +152,63 @@ fn setup_fifo_path() -> PathBuf {
     }
 }
 
+/// Determine the path to use for the saved state.
+fn setup_state_path() -> PathBuf {
+    if let Some(mut buf) = var_os("XDG_RUNTIME_DIR").map(PathBuf::from) {
+        buf.push("gwm_state.toml");
+        buf
+    } else {
+        warn!("couldn't determine the value of $XDG_RUNTIME_DIR, using current dir");
+        PathBuf::from("gwm_state.toml")
+    }
+}
+
+/// Determine the path to use for the configuration file.
+fn setup_config_path() -> PathBuf {
+    if let Some(mut buf) = home_dir() {
+        buf.push(".gwmrc");
+        buf
+    } else {
+        warn!("couldn't determine the value of $HOME, using current dir");
+        PathBuf::from("gwmrc")
+    }
+}
+
+/// Read the configuration from the given path, falling back to defaults if it doesn't exist.
+fn setup_config(path: &Path) -> Config {
+    if path.exists() {
+        Config::from_file(path).unwrap_or_else(|e| e.handle())
+    } else {
+        warn!("config file {} doesn't exist, using defaults", path.display());
+        Config::default()
+    }
+}
+
+/// Replace the process with a new instance of the window manager, using the same arguments.
+///
+/// The binary is looked up by the name it has been started with, so that an upgraded binary is
+/// picked up. The logger configuration is passed on as well.
+fn restart(args: &[String], log_config: Option<OsString>) -> ! {
+    let mut command = Command::new(&args[0]);
+    command.args(&args[1..]);
+
+    if let Some(log_config) = log_config {
+        command.env("RUST_LOG", log_config);
+    }
+
+    info!("restarting");
+    WmError::CouldNotRestart(command.exec()).handle();
+}
+
 /// Main function.
 fn main() {
+    let log_config = var_os("RUST_LOG");
     setup_logger();
 
     let args: Vec<String> = args().collect();
 
     let mut opts = Options::new();
+    opts.optopt("c", "config", "config file to use", "FILE");
     opts.optopt("f", "fifo", "input pipe to use", "FIFO");
     opts.optflag("h", "help", "print this help menu");
 
@@ -158,6 +225,13 @@ fn main() {
         return;
     }
 
+    let config = if let Some(p) = matches.opt_str("c") {
+        setup_config(Path::new(&p))
+    } else {
+        let path = setup_config_path();
+        setup_config(&path)
+    };
+
     let fifo = if let Some(p) = matches.opt_str("f") {
         setup_fifo(Path::new(&p))
     } else {
@@ -174,7 +248,11 @@ fn main() {
 
     setup_sigaction();
 
-    let mut core = WmCore::new(fifo, &con, screen_num);
+    let exit = WmCore::new(fifo, &con, screen_num, config, setup_state_path()).main_loop();
 
-    core.main_loop();
+    if let LoopExit::Restart = exit {
+        // close the connection to the X server cleanly before replacing ourselves
+        drop(con);
+        restart(&args, log_config);
+    }
 }
@@ -33,21 +33,487 @@
  */
 
 use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
 
-use wm::layout::LayoutContainer;
-use wm::tree::{Arena, Geometry};
+use toml::value::{Array, Table, Value};
 
-#[derive(PartialEq, Eq, Hash)]
-pub enum Tag {
-    Work(i8),
-    NonWork,
+use wm::err::{WmError, WmResult};
+use wm::layout::{LayoutContainer, LayoutList};
+use wm::tree::{Arena, Gaps, Geometry};
+
+/// A tag, represented by an interned name.
+///
+/// Tags are declared in the configuration file, but can also be created on the fly when
+/// referred to by name. The names are kept in a `TagRegistry`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Tag(u16);
+
+/// The hidden tag carried by the scratchpad client.
+///
+/// It is always registered first, under the name `scratchpad`, and is never displayed on a
+/// status bar. Clients carrying it are not shown on any tagset.
+pub const SCRATCHPAD: Tag = Tag(0);
+
+/// Information kept about a tag.
+struct TagInfo {
+    /// The name used to refer to the tag in messages.
+    name: String,
+    /// The label used to display the tag on a status bar.
+    label: String,
+    /// The position of the tag when displayed on a status bar.
+    order: i64,
+    /// Whether the tag is hidden from the status bar.
+    hidden: bool,
+}
+
+/// Names that can't be given to tags or used in messages.
+///
+/// `selected` is a keyword of tag editing messages, and `scratchpad` names the hidden tag of
+/// the scratchpad client, which is managed by the window manager alone.
+const RESERVED_TAG_NAMES: &[&str] = &["selected", "scratchpad"];
+
+/// The registry of all tags known, used to intern tag names.
+pub struct TagRegistry {
+    /// The tags known, indexed by values of type `Tag`.
+    tags: Vec<TagInfo>,
+}
+
+impl Default for TagRegistry {
+    fn default() -> Self {
+        TagRegistry {
+            tags: vec![TagInfo {
+                name: "scratchpad".to_owned(),
+                label: "scratchpad".to_owned(),
+                order: 0,
+                hidden: true,
+            }],
+        }
+    }
+}
+
+impl TagRegistry {
+    /// Check whether a name is reserved and can't refer to a tag.
+    pub fn is_reserved(name: &str) -> bool {
+        RESERVED_TAG_NAMES.contains(&name)
+    }
+
+    /// Register a tag with the given name, label and order, unless it exists already.
+    fn declare(&mut self, name: String, label: Option<String>, order: Option<i64>) -> Tag {
+        if let Some(tag) = self.lookup(&name) {
+            return tag;
+        }
+
+        let order = order.unwrap_or_else(|| {
+            self.tags.iter().filter(|t| !t.hidden).map(|t| t.order + 1).max().unwrap_or(0)
+        });
+
+        self.tags.push(TagInfo {
+            label: label.unwrap_or_else(|| name.clone()),
+            name,
+            order,
+            hidden: false,
+        });
+
+        Tag(self.tags.len() as u16 - 1)
+    }
+
+    /// Get the tag with the given name, creating it if necessary.
+    ///
+    /// Tags created on the fly use their name as a label and are ordered after all other tags.
+    pub fn intern(&mut self, name: &str) -> Tag {
+        self.declare(name.to_owned(), None, None)
+    }
+
+    /// Get the tag with the given name, if it exists.
+    pub fn lookup(&self, name: &str) -> Option<Tag> {
+        self.tags.iter().position(|t| t.name == name).map(|i| Tag(i as u16))
+    }
+
+    /// Get the name of a tag.
+    pub fn name(&self, tag: Tag) -> &str {
+        &self.tags[tag.0 as usize].name
+    }
+
+    /// Get the label of a tag.
+    pub fn label(&self, tag: Tag) -> &str {
+        &self.tags[tag.0 as usize].label
+    }
+
+    /// Get all tags known that aren't hidden, in display order.
+    pub fn all(&self) -> Vec<Tag> {
+        let tags: HashSet<Tag> = (0..self.tags.len())
+            .filter(|&i| !self.tags[i].hidden)
+            .map(|i| Tag(i as u16))
+            .collect();
+        self.sorted(&tags)
+    }
+
+    /// Get a set of tags in display order.
+    pub fn sorted(&self, tags: &HashSet<Tag>) -> Vec<Tag> {
+        let mut tags: Vec<Tag> = tags.iter().cloned().collect();
+        tags.sort_by_key(|t| (self.tags[t.0 as usize].order, *t));
+        tags
+    }
+}
+
+/// The policy used when showing a tagset that is already shown on another screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ViewPolicy {
+    /// The other screen shows the tagset previously shown on the focused screen.
+    #[default]
+    Swap,
+    /// The other screen shows the hidden tagset with the smallest id instead.
+    ///
+    /// If no tagset is hidden, the tagsets are swapped.
+    Steal,
+}
+
+/// The size of the scratchpad if none is configured, in percent of the screen size.
+pub const DEFAULT_SCRATCHPAD_SIZE: u32 = 60;
+
+/// The configuration of the scratchpad.
+pub struct ScratchpadConfig {
+    /// The `WM_CLASS` instance or class name identifying the scratchpad client.
+    pub class: String,
+    /// The shell command spawned when no client matches the class.
+    pub command: Option<String>,
+    /// The width of the scratchpad, in percent of the screen's width.
+    pub width: u32,
+    /// The height of the scratchpad, in percent of the screen's height.
+    pub height: u32,
+}
+
+impl ScratchpadConfig {
+    /// Check whether a client with the given `WM_CLASS` is a scratchpad client.
+    pub fn matches(&self, class: &[String]) -> bool {
+        class.contains(&self.class)
+    }
+}
+
+/// The configuration of the window manager, as read from the configuration file.
+pub struct Config {
+    /// The tags declared.
+    pub tags: TagRegistry,
+    /// The tags shown on the initial tagset.
+    pub default_tags: HashSet<Tag>,
+    /// The policy used when showing a tagset shown on another screen.
+    pub view_policy: ViewPolicy,
+    /// The scratchpad configuration, if any.
+    pub scratchpad: Option<ScratchpadConfig>,
+    /// The gaps given to tagsets initially.
+    pub gaps: Gaps,
+    /// The layouts tagsets can switch between, the first one being used initially.
+    pub layouts: Vec<LayoutContainer>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let mut tags = TagRegistry::default();
+        let mut default_tags = HashSet::new();
+        default_tags.insert(tags.intern("default"));
+
+        Config {
+            tags,
+            default_tags,
+            view_policy: ViewPolicy::default(),
+            scratchpad: None,
+            gaps: Gaps::default(),
+            layouts: vec![LayoutContainer::default()],
+        }
+    }
+}
+
+impl Config {
+    /// Read the configuration from a file.
+    ///
+    /// The file is expected to contain an array of tables called `tags`, each with a `name`,
+    /// and an optional `label` and `order`. Reserved names, such as `selected`, are rejected.
+    /// An optional array `default_tags` names the tags shown initially, defaulting to the first
+    /// tag declared. The optional `view_policy` is either `"swap"` or `"steal"`. An optional
+    /// table `scratchpad` has a `class`, and an optional `command`, `width` and `height`, the
+    /// latter two in percent of the screen size. An optional table `gaps` has an optional
+    /// `inner` and `outer` gap in pixels, and an optional boolean `smart`. The optional array
+    /// `layouts` lists the layouts each tagset can switch between, in their word-based
+    /// representation, defaulting to the manual layout.
+    pub fn from_file(path: &Path) -> WmResult<Config> {
+        let mut table = parse_file(path)?;
+        let mut tags = TagRegistry::default();
+
+        for tag in extract_array(&mut table, "tags")? {
+            let mut tag = match tag {
+                Value::Table(t) => t,
+                _ => return Err(WmError::KeyTypeMismatch("tags".to_owned())),
+            };
+
+            let name = extract_string(&mut tag, "name")?;
+            if TagRegistry::is_reserved(&name) {
+                return Err(WmError::KeyTypeMismatch("tags".to_owned()));
+            }
+
+            let label = opt_key(extract_string(&mut tag, "label"))?;
+            let order = opt_key(extract_int(&mut tag, "order"))?;
+
+            tags.declare(name, label, order);
+        }
+
+        let default_tags = match opt_key(extract_array(&mut table, "default_tags"))? {
+            Some(names) => {
+                let mut default_tags = HashSet::new();
+
+                for name in names {
+                    match name {
+                        Value::String(ref name) if !TagRegistry::is_reserved(name) => {
+                            default_tags.insert(tags.intern(name))
+                        },
+                        _ => return Err(WmError::KeyTypeMismatch("default_tags".to_owned())),
+                    };
+                }
+
+                default_tags
+            },
+            None => tags.all().into_iter().take(1).collect(),
+        };
+
+        if default_tags.is_empty() {
+            return Err(WmError::KeyMissing("default_tags".to_owned()));
+        }
+
+        let view_policy = match opt_key(extract_string(&mut table, "view_policy"))? {
+            Some(ref p) if p == "swap" => ViewPolicy::Swap,
+            Some(ref p) if p == "steal" => ViewPolicy::Steal,
+            Some(_) => return Err(WmError::KeyTypeMismatch("view_policy".to_owned())),
+            None => ViewPolicy::default(),
+        };
+
+        let scratchpad = match opt_key(extract_table(&mut table, "scratchpad"))? {
+            Some(mut scratchpad) => Some(ScratchpadConfig {
+                class: extract_string(&mut scratchpad, "class")?,
+                command: opt_key(extract_string(&mut scratchpad, "command"))?,
+                width: extract_percentage(&mut scratchpad, "width")?,
+                height: extract_percentage(&mut scratchpad, "height")?,
+            }),
+            None => None,
+        };
+
+        let gaps = match opt_key(extract_table(&mut table, "gaps"))? {
+            Some(mut gaps) => Gaps {
+                inner: extract_pixels(&mut gaps, "inner")?,
+                outer: extract_pixels(&mut gaps, "outer")?,
+                smart: opt_key(extract_bool(&mut gaps, "smart"))?.unwrap_or(false),
+            },
+            None => Gaps::default(),
+        };
+
+        let mut layouts = Vec::new();
+
+        for layout in opt_key(extract_array(&mut table, "layouts"))?.unwrap_or_default() {
+            let layout = match layout {
+                Value::String(layout) => {
+                    let words: Vec<&str> = layout.split_whitespace().collect();
+                    LayoutContainer::parse_from_words(&words)
+                },
+                _ => None,
+            };
+
+            match layout {
+                Some(layout) => layouts.push(layout),
+                None => return Err(WmError::KeyTypeMismatch("layouts".to_owned())),
+            }
+        }
+
+        if layouts.is_empty() {
+            layouts.push(LayoutContainer::default());
+        }
+
+        Ok(Config {
+            tags,
+            default_tags,
+            view_policy,
+            scratchpad,
+            gaps,
+            layouts,
+        })
+    }
+}
+
+/// Try to parse a TOML table from a config file, given as a path.
+fn parse_file(path: &Path) -> WmResult<Table> {
+    let mut toml_str = String::new();
+
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut toml_str))
+        .map_err(WmError::ConfigIOError)?;
+
+    match toml_str.parse::<Value>().map_err(WmError::TomlError)? {
+        Value::Table(t) => Ok(t),
+        _ => Err(WmError::TomlNotTable),
+    }
+}
+
+/// Extract a key's value from a table.
+pub fn extract_value(table: &mut Table, key: &str) -> WmResult<Value> {
+    table.remove(key).ok_or_else(|| WmError::KeyMissing(key.to_owned()))
+}
+
+/// Extract a key's value from a table as an int.
+pub fn extract_int(table: &mut Table, key: &str) -> WmResult<i64> {
+    as_int(extract_value(table, key)?, key)
+}
+
+/// Extract a key's value from a table as a string.
+pub fn extract_string(table: &mut Table, key: &str) -> WmResult<String> {
+    as_string(extract_value(table, key)?, key)
+}
+
+/// Extract a key's value from a table as a boolean.
+pub fn extract_bool(table: &mut Table, key: &str) -> WmResult<bool> {
+    as_bool(extract_value(table, key)?, key)
+}
+
+/// Extract a key's value from a table as an array.
+pub fn extract_array(table: &mut Table, key: &str) -> WmResult<Array> {
+    match extract_value(table, key)? {
+        Value::Array(a) => Ok(a),
+        _ => Err(WmError::KeyTypeMismatch(key.to_owned())),
+    }
+}
+
+/// Extract a key's value from a table as an array of strings.
+pub fn extract_strings(table: &mut Table, key: &str) -> WmResult<Vec<String>> {
+    extract_array(table, key)?.into_iter().map(|v| as_string(v, key)).collect()
+}
+
+/// Extract a key's value from a table as a table.
+pub fn extract_table(table: &mut Table, key: &str) -> WmResult<Table> {
+    as_table(extract_value(table, key)?, key)
+}
+
+/// Interpret a value found under the given key as an integer of the given type.
+pub fn as_int<T: TryFrom<i64>>(value: Value, key: &str) -> WmResult<T> {
+    match value {
+        Value::Integer(i) => T::try_from(i).map_err(|_| WmError::KeyTypeMismatch(key.to_owned())),
+        _ => Err(WmError::KeyTypeMismatch(key.to_owned())),
+    }
+}
+
+/// Interpret a value found under the given key as a string.
+pub fn as_string(value: Value, key: &str) -> WmResult<String> {
+    match value {
+        Value::String(s) => Ok(s),
+        _ => Err(WmError::KeyTypeMismatch(key.to_owned())),
+    }
+}
+
+/// Interpret a value found under the given key as a boolean.
+pub fn as_bool(value: Value, key: &str) -> WmResult<bool> {
+    match value {
+        Value::Boolean(b) => Ok(b),
+        _ => Err(WmError::KeyTypeMismatch(key.to_owned())),
+    }
+}
+
+/// Interpret a value found under the given key as a table.
+pub fn as_table(value: Value, key: &str) -> WmResult<Table> {
+    match value {
+        Value::Table(t) => Ok(t),
+        _ => Err(WmError::KeyTypeMismatch(key.to_owned())),
+    }
+}
+
+/// Extract an optional percentage from a table, defaulting to `DEFAULT_SCRATCHPAD_SIZE`.
+fn extract_percentage(table: &mut Table, key: &str) -> WmResult<u32> {
+    match opt_key(extract_int(table, key))? {
+        Some(p) if p > 0 && p <= 100 => Ok(p as u32),
+        Some(_) => Err(WmError::KeyTypeMismatch(key.to_owned())),
+        None => Ok(DEFAULT_SCRATCHPAD_SIZE),
+    }
 }
 
-pub fn arena_init(default_screen_geometry: Geometry) -> Arena {
-    let mut default_tagset = HashSet::new();
-    default_tagset.insert(Tag::NonWork);
+/// Extract an optional, non-negative amount of pixels from a table, defaulting to 0.
+fn extract_pixels(table: &mut Table, key: &str) -> WmResult<u32> {
+    match opt_key(extract_int(table, key))? {
+        Some(p) if p >= 0 && p <= i64::from(u16::MAX) => Ok(p as u32),
+        Some(_) => Err(WmError::KeyTypeMismatch(key.to_owned())),
+        None => Ok(0),
+    }
+}
+
+/// Check for an optional key to extract.
+pub fn opt_key<T>(input_result: WmResult<T>) -> WmResult<Option<T>> {
+    match input_result {
+        Ok(res) => Ok(Some(res)),
+        Err(WmError::KeyMissing(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Set up an arena from the configuration, with a screen for each of the geometries given.
+///
+/// At least one geometry has to be given, the first one is used for the default screen.
+pub fn arena_init(config: Config, screen_geometries: &[Geometry]) -> Arena {
+    let default_layouts = LayoutList::new(config.layouts);
+    let (&default_screen_geometry, other_screen_geometries) =
+        screen_geometries.split_first().expect("no screens given");
+
+    let mut arena =
+        Arena::new(config.tags, config.default_tags, default_layouts, default_screen_geometry);
+
+    for &geometry in other_screen_geometries {
+        arena.add_screen(geometry);
+    }
+
+    arena.set_view_policy(config.view_policy);
+    arena.set_scratchpad(config.scratchpad);
+    arena.set_gaps(config.gaps);
+
+    arena
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+    use std::process;
+
+    fn config(name: &str, contents: &str) -> WmResult<Config> {
+        let path = env::temp_dir().join(format!("gwm-test-{}-{}.toml", process::id(), name));
+        File::create(&path).and_then(|mut f| f.write_all(contents.as_bytes())).unwrap();
+        let config = Config::from_file(&path);
+        fs::remove_file(&path).unwrap();
+        config
+    }
+
+    #[test]
+    fn tag_registry() {
+        let mut tags = TagRegistry::default();
+        assert_eq!(tags.lookup("scratchpad"), Some(SCRATCHPAD));
+        assert!(tags.all().is_empty());
+
+        let web = tags.declare("web".to_owned(), Some("www".to_owned()), Some(5));
+        let mail = tags.intern("mail");
+        let chat = tags.declare("chat".to_owned(), None, Some(1));
+        assert_eq!(tags.intern("web"), web);
+        assert_eq!(tags.lookup("mail"), Some(mail));
+        assert_eq!(tags.lookup("music"), None);
+        assert_eq!((tags.name(web), tags.label(web)), ("web", "www"));
+        assert_eq!(tags.all(), [chat, web, mail]);
 
-    let default_layout = LayoutContainer::default();
+        let shown = [SCRATCHPAD, mail, web].iter().cloned().collect();
+        assert_eq!(tags.sorted(&shown), [SCRATCHPAD, web, mail]);
+    }
 
-    Arena::new(default_tagset, default_layout, default_screen_geometry)
+    #[test]
+    fn reserved_tag_names_are_rejected() {
+        assert!(config("plain", "tags = [{ name = \"web\" }]").is_ok());
+        assert!(config("declared", "tags = [{ name = \"selected\" }]").is_err());
+        assert!(config("scratchpad", "tags = [{ name = \"scratchpad\" }]").is_err());
+        assert!(config("default",
+                       "tags = [{ name = \"web\" }]\ndefault_tags = [\"selected\"]").is_err());
+    }
 }
@@ -32,16 +32,29 @@
  * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
  */
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{stdout, BufRead, BufReader, ErrorKind};
 use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use libc;
 
 use xcb::base::*;
+use xcb::randr;
+use xcb::xproto;
 
-use wm::config;
-use wm::msg::Message;
-use wm::tree::Arena;
+use wm::config::{self, Config};
+use wm::err::WmError;
+use wm::msg::{GenericMessage, Message};
+use wm::state::ArenaState;
+use wm::tree::{Arena, Client, ClientId, ClientProperties, Geometry, ScratchpadToggle};
+
+/// The ICCCM `NormalState` of a window.
+const NORMAL_STATE: u32 = 1;
+
+/// The ICCCM `IconicState` of a window.
+const ICONIC_STATE: u32 = 3;
 
 /// Construct a `pollfd` struct from a file reference.
 fn setup_pollfd_from_file(fd: &File) -> libc::pollfd {
@@ -70,6 +83,55 @@ fn poll(fds: &mut [libc::pollfd]) -> bool {
     poll_res > 0
 }
 
+/// Get the geometries of all active CRTCs, as reported by RANDR.
+///
+/// CRTCs without outputs are skipped, and mirrored outputs are only reported once. Returns an
+/// empty vector if RANDR is unavailable.
+fn screen_geometries(con: &Connection, root: xproto::Window) -> Vec<Geometry> {
+    match randr::query_version(con, 1, 2).get_reply() {
+        Ok(ref r) if r.major_version() == 1 && r.minor_version() >= 2 => (),
+        _ => return Vec::new(),
+    }
+
+    let resources = match randr::get_screen_resources_current(con, root).get_reply() {
+        Ok(resources) => resources,
+        Err(_) => return Vec::new(),
+    };
+
+    let cookies: Vec<_> = resources
+        .crtcs()
+        .iter()
+        .map(|&crtc| randr::get_crtc_info(con, crtc, resources.config_timestamp()))
+        .collect();
+
+    let mut geometries = Vec::new();
+
+    for cookie in cookies {
+        let info = match cookie.get_reply() {
+            Ok(info) => info,
+            Err(_) => continue,
+        };
+
+        if info.num_outputs() == 0 || info.width() == 0 || info.height() == 0 {
+            continue;
+        }
+
+        let geometry = Geometry {
+            x: info.x().max(0) as u32,
+            y: info.y().max(0) as u32,
+            width: u32::from(info.width()),
+            height: u32::from(info.height()),
+        };
+
+        if !geometries.contains(&geometry) {
+            debug!("found screen: {:?}", geometry);
+            geometries.push(geometry);
+        }
+    }
+
+    geometries
+}
+
 /// The possible input events we get from a command input handler.
 pub enum InputResult<'a> {
     /// The words handed down by the iterator have been read from the input pipe.
@@ -129,22 +191,71 @@ impl CommandInput {
     }
 }
 
+/// The atoms used by the window manager, interned on startup.
+struct Atoms {
+    /// `WM_STATE`, the ICCCM window state property.
+    wm_state: xproto::Atom,
+    /// `WM_CHANGE_STATE`, the ICCCM client message used by clients to iconify themselves.
+    wm_change_state: xproto::Atom,
+}
+
+impl Atoms {
+    /// Intern all atoms used.
+    fn new(con: &Connection) -> Atoms {
+        let intern = |name: &str| {
+            xproto::intern_atom(con, false, name)
+                .get_reply()
+                .map(|r| r.atom())
+                .unwrap_or_else(|_| WmError::CouldNotInternAtom(name.to_owned()).handle())
+        };
+
+        Atoms {
+            wm_state: intern("WM_STATE"),
+            wm_change_state: intern("WM_CHANGE_STATE"),
+        }
+    }
+}
+
+/// Set once the window manager has been asked to exit by a signal.
+///
+/// Signals interrupt `poll(3)`, after which the main loop checks this flag.
+pub static TERMINATE: AtomicBool = AtomicBool::new(false);
+
+/// The reason the main loop has been left.
+pub enum LoopExit {
+    /// The window manager is to exit.
+    Quit,
+    /// The window manager is to be replaced by a new instance.
+    Restart,
+}
+
 /// The core structure handling the X connection and messaging.
 ///
 /// Responsible for handling events from X and messages from the FIFO, as well as to dispatch
 /// messages to the appropriate datastructures, and to push the corresponding changes to X.
-pub struct WmCore {
+pub struct WmCore<'a> {
+    /// The connection to the X server.
+    con: &'a Connection,
     /// The input source to use.
     input: CommandInput,
-    // The root window.
-    // root: Window,
+    /// The atoms used.
+    atoms: Atoms,
     /// The place where all the internal tree datastructures play.
     arena: Arena,
+    /// The path of the file the arena's state is saved to.
+    state_path: PathBuf,
 }
 
-impl WmCore {
+impl<'a> WmCore<'a> {
     /// Construct a new window manager core object from the necessary parameters.
-    pub fn new(fifo: File, con: &Connection, screen_num: i32) -> WmCore {
+    ///
+    /// All windows already present are adopted, restoring the state saved at the given path,
+    /// if any.
+    pub fn new(fifo: File,
+               con: &'a Connection,
+               screen_num: i32,
+               config: Config,
+               state_path: PathBuf) -> WmCore<'a> {
         // TODO: error handling.
         let screen = con.get_setup().roots().nth(screen_num as usize).unwrap();
 
@@ -168,33 +279,441 @@ impl WmCore {
             (_, Err(_), _) | (_, _, None) => Err(WmError::RandRVersionMismatch),
         } */
 
-        WmCore {
+        let values = [(xproto::CW_EVENT_MASK,
+                       xproto::EVENT_MASK_SUBSTRUCTURE_REDIRECT |
+                       xproto::EVENT_MASK_SUBSTRUCTURE_NOTIFY)];
+
+        if xproto::change_window_attributes_checked(con, screen.root(), &values)
+            .request_check()
+            .is_err()
+        {
+            WmError::OtherWMRunning.handle();
+        }
+
+        let mut geometries = screen_geometries(con, screen.root());
+
+        if geometries.is_empty() {
+            info!("no RANDR outputs found, using the whole root window as a single screen");
+            geometries.push(Geometry {
+                x: 0,
+                y: 0,
+                width: u32::from(screen.width_in_pixels()),
+                height: u32::from(screen.height_in_pixels()),
+            });
+        }
+
+        let mut core = WmCore {
+            con,
             input: CommandInput::new(fifo, con),
-            // root: screen.root(),
-            arena: config::arena_init(Default::default()), // TODO
+            atoms: Atoms::new(con),
+            arena: config::arena_init(config, &geometries),
+            state_path,
+        };
+
+        core.adopt_windows(screen.root());
+
+        core
+    }
+
+    /// Manage all windows already present, restoring the saved state if possible.
+    ///
+    /// Windows are adopted if they are mapped, or if they have been iconified by us before.
+    fn adopt_windows(&mut self, root: xproto::Window) {
+        let windows = xproto::query_tree(self.con, root)
+            .get_reply()
+            .map(|r| r.children().to_vec())
+            .unwrap_or_default();
+
+        let clients: Vec<Client> = windows
+            .into_iter()
+            .map(ClientId)
+            .filter_map(|w| self.adoptable_state(w).map(|mapped| (w, mapped)))
+            .map(|(w, mapped)| {
+                // rendering only touches the state of windows changing their mapping
+                if mapped {
+                    self.set_wm_state(w, NORMAL_STATE);
+                }
+
+                let mut client = self.new_client(w);
+                client.set_mapped(mapped);
+                client
+            })
+            .collect();
+
+        // the state is saved whenever we exit, so it is kept until it is replaced on the next exit
+        match ArenaState::load(&self.state_path) {
+            Ok(state) => {
+                info!("restoring state from {}", self.state_path.display());
+                self.arena.load_snapshot(state, clients);
+            },
+            Err(e) => {
+                match e {
+                    WmError::StateIOError(ref e) if e.kind() == ErrorKind::NotFound => (),
+                    e => {
+                        warn!("could not restore state from {}", self.state_path.display());
+                        e.log();
+                    },
+                }
+
+                for client in clients {
+                    self.arena.add_client(client);
+                }
+            },
+        }
+
+        self.render();
+    }
+
+    /// Check whether a window present on startup is to be managed.
+    ///
+    /// Returns whether the window is mapped if it is to be managed, and `None` otherwise.
+    fn adoptable_state(&self, window: ClientId) -> Option<bool> {
+        let attrs = xproto::get_window_attributes(self.con, window.0).get_reply().ok()?;
+
+        if attrs.override_redirect() {
+            return None;
+        }
+
+        if attrs.map_state() == xproto::MAP_STATE_VIEWABLE as u8 {
+            return Some(true);
+        }
+
+        let cookie = xproto::get_property(
+            self.con, false, window.0, self.atoms.wm_state, self.atoms.wm_state, 0, 2);
+
+        match cookie.get_reply() {
+            Ok(ref reply) if reply.value::<u32>().first() == Some(&ICONIC_STATE) => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Show or hide the scratchpad, spawning the scratchpad command if no client exists.
+    ///
+    /// Returns `true` if the screens have to be rendered again.
+    fn toggle_scratchpad(&mut self) -> bool {
+        match self.arena.toggle_scratchpad() {
+            ScratchpadToggle::Shown | ScratchpadToggle::Hidden => true,
+            ScratchpadToggle::Spawn(command) => {
+                // the client is shown once it appears, nothing to render yet
+                if let Err(e) = Command::new("sh").arg("-c").arg(&command).spawn() {
+                    warn!("could not spawn scratchpad command `{}`: {}", command, e);
+                    self.arena.cancel_scratchpad();
+                }
+                false
+            },
+            ScratchpadToggle::Missing => {
+                info!("no scratchpad client found");
+                false
+            },
+        }
+    }
+
+    /// Save the state of the arena, to be restored on the next start.
+    fn save_state(&self) {
+        match self.arena.snapshot().save(&self.state_path) {
+            Ok(()) => info!("saved state to {}", self.state_path.display()),
+            Err(_) => warn!("could not save state to {}", self.state_path.display()),
         }
     }
 
     /// Run the window manager's main loop, listening to X events and commands from the FIFO.
-    pub fn main_loop(&mut self) {
+    ///
+    /// The state of the arena is saved before the loop is left, be it on request, because of a
+    /// signal, or because the connection to the X server has been lost.
+    pub fn main_loop(&mut self) -> LoopExit {
         loop {
             match self.input.get_next() {
                 InputResult::InputRead(words) => {
                     if let Some(msg) = Message::parse_from_words(&words) {
-                        match_message!(msg, inner_msg => {
+                        match_message!(msg, ref inner_msg => {
                             debug!("received msg: {:?}", inner_msg);
                         });
+
+                        match msg {
+                            Message::GenericMessage(GenericMessage::ToggleScratchpad) => {
+                                if self.toggle_scratchpad() {
+                                    self.render();
+                                    self.log_tree();
+                                }
+                            },
+                            Message::GenericMessage(GenericMessage::SaveState) => {
+                                self.save_state();
+                            },
+                            Message::GenericMessage(GenericMessage::Quit) => {
+                                self.save_state();
+                                return LoopExit::Quit;
+                            },
+                            Message::GenericMessage(GenericMessage::Restart) => {
+                                self.save_state();
+                                return LoopExit::Restart;
+                            },
+                            msg => if self.arena.accept_msg(msg) {
+                                self.render();
+                                self.log_tree();
+                            },
+                        }
+
+                        self.print_status();
                     } else {
                         debug!("received words: {:?}", words);
                     }
                 },
                 InputResult::XFdReadable => {
-                    debug!("X event received");
+                    let mut changed = false;
+
+                    while let Some(event) = self.con.poll_for_event() {
+                        changed |= self.handle_event(&event);
+                    }
+
+                    if self.con.has_error().is_err() {
+                        warn!("lost the connection to the X server, exiting");
+                        self.save_state();
+                        return LoopExit::Quit;
+                    }
+
+                    if changed {
+                        self.render();
+                        self.print_status();
+                    }
                 },
                 InputResult::PollError => {
+                    if TERMINATE.load(Ordering::SeqCst) {
+                        info!("terminated by a signal, exiting");
+                        self.save_state();
+                        return LoopExit::Quit;
+                    }
+
                     debug!("poll(3) returned an error");
                 },
             }
         }
     }
+
+    /// Handle an event from the X server and signify whether the clients have to be rendered
+    /// again.
+    fn handle_event(&mut self, event: &GenericEvent) -> bool {
+        match event.response_type() & !0x80 {
+            xproto::MAP_REQUEST => {
+                let event = unsafe { cast_event::<xproto::MapRequestEvent>(event) };
+                self.manage(ClientId(event.window()))
+            },
+            xproto::UNMAP_NOTIFY => {
+                let event = unsafe { cast_event::<xproto::UnmapNotifyEvent>(event) };
+                let window = ClientId(event.window());
+
+                // unmaps we haven't requested ourselves are clients withdrawing their window
+                if self.arena.client(window).is_some() && !self.arena.unmap_expected(window) {
+                    xproto::delete_property(self.con, window.0, self.atoms.wm_state);
+                    self.arena.remove_client(window);
+                    true
+                } else {
+                    false
+                }
+            },
+            xproto::CONFIGURE_REQUEST => {
+                let event = unsafe { cast_event::<xproto::ConfigureRequestEvent>(event) };
+                self.configure_request(event);
+                false
+            },
+            xproto::DESTROY_NOTIFY => {
+                let event = unsafe { cast_event::<xproto::DestroyNotifyEvent>(event) };
+                self.arena.remove_client(ClientId(event.window())).is_some()
+            },
+            xproto::PROPERTY_NOTIFY => {
+                let event = unsafe { cast_event::<xproto::PropertyNotifyEvent>(event) };
+                let window = ClientId(event.window());
+
+                // the title is shown in the status, so that tab lists are kept current
+                if event.atom() == xproto::ATOM_WM_NAME && self.arena.client(window).is_some() {
+                    let name = self.get_strings(window, xproto::ATOM_WM_NAME).pop();
+                    self.arena.set_client_name(window, name.unwrap_or_default())
+                } else {
+                    false
+                }
+            },
+            xproto::CLIENT_MESSAGE => {
+                let event = unsafe { cast_event::<xproto::ClientMessageEvent>(event) };
+
+                if event.type_() == self.atoms.wm_change_state &&
+                    event.data().data32()[0] == ICONIC_STATE
+                {
+                    self.arena.iconify(ClientId(event.window()))
+                } else {
+                    false
+                }
+            },
+            t => {
+                debug!("X event received: {}", t);
+                false
+            },
+        }
+    }
+
+    /// Start managing a window that requested to be mapped, showing it on the focused tagset.
+    ///
+    /// Hidden clients mapping their window again are restored.
+    fn manage(&mut self, window: ClientId) -> bool {
+        match self.arena.client(window).map(Client::is_iconic) {
+            Some(true) => return self.arena.restore(Some(window)),
+            Some(false) => return false,
+            None => (),
+        }
+
+        let client = self.new_client(window);
+        self.arena.add_client(client);
+
+        true
+    }
+
+    /// Answer a window's request to be configured.
+    ///
+    /// Windows not managed get what they asked for. Clients are told their current geometry
+    /// instead, as it is determined by the layout.
+    fn configure_request(&self, event: &xproto::ConfigureRequestEvent) {
+        let window = ClientId(event.window());
+
+        if let Some(geo) = self.arena.client(window).map(Client::geometry) {
+            let notify = xproto::ConfigureNotifyEvent::new(window.0,
+                                                           window.0,
+                                                           NONE,
+                                                           geo.x as i16,
+                                                           geo.y as i16,
+                                                           geo.width as u16,
+                                                           geo.height as u16,
+                                                           0,
+                                                           false);
+            xproto::send_event(
+                self.con, false, window.0, xproto::EVENT_MASK_STRUCTURE_NOTIFY, &notify);
+        } else {
+            let mask = event.value_mask();
+            let values: Vec<(u16, u32)> = [
+                (xproto::CONFIG_WINDOW_X, event.x() as u32),
+                (xproto::CONFIG_WINDOW_Y, event.y() as u32),
+                (xproto::CONFIG_WINDOW_WIDTH, u32::from(event.width())),
+                (xproto::CONFIG_WINDOW_HEIGHT, u32::from(event.height())),
+                (xproto::CONFIG_WINDOW_BORDER_WIDTH, u32::from(event.border_width())),
+                (xproto::CONFIG_WINDOW_SIBLING, event.sibling()),
+                (xproto::CONFIG_WINDOW_STACK_MODE, u32::from(event.stack_mode())),
+            ]
+                .iter()
+                .map(|&(flag, value)| (flag as u16, value))
+                .filter(|&(flag, _)| mask & flag != 0)
+                .collect();
+
+            xproto::configure_window(self.con, window.0, &values);
+        }
+
+        self.con.flush();
+    }
+
+    /// Construct a client for a window, shown on the focused tagset.
+    ///
+    /// The window's property changes are selected, so that it's title can be kept current.
+    fn new_client(&self, window: ClientId) -> Client {
+        let values = [(xproto::CW_EVENT_MASK, xproto::EVENT_MASK_PROPERTY_CHANGE)];
+        xproto::change_window_attributes(self.con, window.0, &values);
+
+        let geometry = xproto::get_geometry(self.con, window.0)
+            .get_reply()
+            .map(|g| Geometry {
+                x: g.x().max(0) as u32,
+                y: g.y().max(0) as u32,
+                width: u32::from(g.width()),
+                height: u32::from(g.height()),
+            })
+            .unwrap_or_default();
+
+        let properties = ClientProperties {
+            name: self.get_strings(window, xproto::ATOM_WM_NAME).pop().unwrap_or_default(),
+            class: self.get_strings(window, xproto::ATOM_WM_CLASS),
+        };
+
+        let tags = {
+            let tagset = self.arena.focused_tagset();
+            tagset.tags().union(tagset.required()).cloned().collect()
+        };
+
+        Client::new(window, geometry, properties, tags)
+    }
+
+    /// Get a string property of a window, split at null bytes.
+    fn get_strings(&self, window: ClientId, property: xproto::Atom) -> Vec<String> {
+        let cookie =
+            xproto::get_property(self.con, false, window.0, property, xproto::ATOM_ANY, 0, 1024);
+
+        match cookie.get_reply() {
+            Ok(reply) => reply
+                .value::<u8>()
+                .split(|&b| b == 0)
+                .filter(|s| !s.is_empty())
+                .map(|s| String::from_utf8_lossy(s).into_owned())
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Set the ICCCM `WM_STATE` property of a client.
+    fn set_wm_state(&self, client: ClientId, state: u32) {
+        let data = [state, NONE];
+        xproto::change_property(self.con,
+                                xproto::PROP_MODE_REPLACE as u8,
+                                client.0,
+                                self.atoms.wm_state,
+                                self.atoms.wm_state,
+                                32,
+                                &data);
+    }
+
+    /// Rearrange all visible clients and push the resulting geometries to the X server.
+    fn render(&mut self) {
+        let (sizes, mapped, unmapped) = self.arena.arrange();
+
+        for (client, geo) in &sizes {
+            let values = [
+                (xproto::CONFIG_WINDOW_X as u16, geo.x),
+                (xproto::CONFIG_WINDOW_Y as u16, geo.y),
+                (xproto::CONFIG_WINDOW_WIDTH as u16, geo.width),
+                (xproto::CONFIG_WINDOW_HEIGHT as u16, geo.height),
+            ];
+
+            xproto::configure_window(self.con, client.0, &values);
+        }
+
+        for client in mapped {
+            xproto::map_window(self.con, client.0);
+            self.set_wm_state(client, NORMAL_STATE);
+        }
+
+        if let Some(client) = self.arena.scratchpad_client_shown() {
+            let values = [(xproto::CONFIG_WINDOW_STACK_MODE as u16, xproto::STACK_MODE_ABOVE)];
+            xproto::configure_window(self.con, client.0, &values);
+        }
+
+        // clients not shown on any screen are considered iconified as far as ICCCM is concerned
+        for client in unmapped {
+            xproto::unmap_window(self.con, client.0);
+            self.arena.expect_unmap(client);
+            self.set_wm_state(client, ICONIC_STATE);
+        }
+
+        self.con.flush();
+    }
+
+    /// Log a dump of the tag tree shown on the focused screen.
+    fn log_tree(&self) {
+        let mut dump = Vec::new();
+
+        if self.arena.write_tree(&mut dump).is_ok() {
+            debug!("tree:\n{}", String::from_utf8_lossy(&dump));
+        }
+    }
+
+    /// Write the current status to the status stream on `stdout`.
+    fn print_status(&self) {
+        let out = stdout();
+
+        if self.arena.write_status(&mut out.lock()).is_err() {
+            warn!("could not write status");
+        }
+    }
 }
@@ -1,4 +1,7 @@
+use std::io::Error as IoError;
+
 use getopts::Fail;
+use toml;
 use xcb::base;
 
 pub enum WmError {
@@ -8,25 +11,49 @@ pub enum WmError {
     CouldNotConnect(base::ConnError),
     CouldNotAcquireScreen,
     OtherWMRunning,
+    CouldNotInternAtom(String),
+    CouldNotRestart(IoError),
     ConnectionInterrupted,
     IOError,
+    ConfigIOError(IoError),
+    StateIOError(IoError),
+    TomlError(toml::de::Error),
+    TomlNotTable,
+    KeyMissing(String),
+    KeyTypeMismatch(String),
 }
 
 impl WmError {
+    /// Log the error and exit.
     pub fn handle(self) -> ! {
+        self.log();
+        ::std::process::exit(1);
+    }
+
+    /// Log the error, for errors that can be recovered from.
+    pub fn log(&self) {
         use wm::err::WmError::*;
 
-        match self {
-            CouldNotParseOptions(f) => error!("{}", f),
+        match *self {
+            CouldNotParseOptions(ref f) => error!("{}", f),
             CouldNotEstablishSignalHandlers => error!("could not establish signal handlers"),
             CouldNotOpenPipe => error!("could not open pipe"),
-            CouldNotConnect(e) => error!("could not connect: {}", e),
+            CouldNotConnect(ref e) => error!("could not connect: {}", e),
             CouldNotAcquireScreen => error!("could not acquire screen"),
             OtherWMRunning => error!("another wm is running"),
+            CouldNotInternAtom(ref a) => error!("could not intern atom {}", a),
+            CouldNotRestart(ref e) => error!("could not restart: {}", e),
             ConnectionInterrupted => error!("connection interrupted"),
             IOError => error!("I/O error occured"),
+            ConfigIOError(ref i) => error!("could not read config: {}", i),
+            StateIOError(ref i) => error!("could not access saved state: {}", i),
+            TomlError(ref t) => error!("TOML parsing of config failed: {}", t),
+            TomlNotTable => error!("config is not a table at the top level"),
+            KeyMissing(ref k) => error!("missing config key: {}", k),
+            KeyTypeMismatch(ref k) => error!("key {} has incorrect type", k),
         }
-
-        ::std::process::exit(1);
     }
 }
+
+/// A result returned when reading in the configuration.
+pub type WmResult<T> = Result<T, WmError>;
@@ -0,0 +1,212 @@
+/*
+ * Copyright Inokentiy Babushkin and contributors (c) 2016-2017
+ *
+ * All rights reserved.
+
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions
+ * are met:
+ *
+ *     * Redistributions of source code must retain the above copyright
+ *       notice, this list of conditions and the following disclaimer.
+ *
+ *     * Redistributions in binary form must reproduce the above
+ *       copyright notice, this list of conditions and the following
+ *       disclaimer in the documentation and/or other materials provided
+ *       with the distribution.
+ *
+ *     * Neither the name of Inokentiy Babushkin nor the names of other
+ *       contributors may be used to endorse or promote products derived
+ *       from this software without specific prior written permission.
+
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+ * "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+ * LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+ * A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+ * OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+ * LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+ * DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+ * THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+use std::cmp::min;
+use std::str::FromStr;
+
+use wm::layout::{flat_container_by_direction, tiled_clients, Layout};
+use wm::msg::{Message, RatioMessage};
+use wm::tree::{ContainerId, Direction, Geometry, Screen, TagTree, WindowSizes};
+
+/// The percentage of the remaining space taken by each client, by default.
+const DEFAULT_RATIO: u8 = 50;
+
+/// Parse the split ratio of a fibonacci layout from its list of parameters.
+fn parse_ratio(words: &[&str]) -> Option<u8> {
+    match words {
+        [] => Some(DEFAULT_RATIO),
+        [ratio] => u8::from_str(ratio).ok().filter(|&r| r <= 100),
+        _ => None,
+    }
+}
+
+/// Apply a ratio message to a split ratio.
+fn apply_ratio(ratio: &mut u8, msg: Message) -> bool {
+    match msg {
+        Message::RatioMessage(msg) => {
+            *ratio = match msg {
+                RatioMessage::Absolute(r) => min(r, 100),
+                RatioMessage::Increase(r) => min(ratio.saturating_add(r), 100),
+                RatioMessage::Decrease(r) => ratio.saturating_sub(r),
+            };
+            true
+        },
+        _ => false,
+    }
+}
+
+/// Compute the geometries of the tiled clients in a flat tree, fibonacci-style.
+///
+/// Each client but the last takes a part of the remaining area, alternating between the left
+/// and the upper part. If `spiral` is set, the right and the lower part are taken every other
+/// time, making the clients spiral inwards. The ratio is kept away from the extremes, so that
+/// no client collapses entirely.
+fn arrange(tree: &TagTree, mut geo: Geometry, ratio: u8, spiral: bool, sizes: &mut WindowSizes) {
+    let clients = tiled_clients(tree);
+    let ratio = u32::from(ratio.clamp(1, 99));
+
+    for (i, &(client, _)) in clients.iter().enumerate() {
+        if i + 1 == clients.len() {
+            sizes.insert(client, geo);
+            break;
+        }
+
+        let reversed = spiral && i % 4 >= 2;
+        let weights = if reversed { [100 - ratio, ratio] } else { [ratio, 100 - ratio] };
+        let parts = if i % 2 == 0 {
+            geo.split_horizontal(&weights)
+        } else {
+            geo.split_vertical(&weights)
+        };
+
+        let (taken, rest) = if reversed { (parts[1], parts[0]) } else { (parts[0], parts[1]) };
+        sizes.insert(client, taken);
+        geo = rest;
+    }
+}
+
+/// Declare a fibonacci layout holding a flat tree.
+macro_rules! declare_fibonacci_layout {
+    ($(#[$attr:meta])* $name:ident; $spiral:expr) => {
+        $(#[$attr])*
+        #[derive(PartialEq, Eq, Clone, Debug)]
+        pub struct $name {
+            /// The percentage of the remaining area taken by each client.
+            ratio: u8,
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                $name { ratio: DEFAULT_RATIO }
+            }
+        }
+
+        impl $name {
+            /// Parse the layout from its list of parameters, an optional split ratio.
+            pub fn parse_from_words(words: &[&str]) -> Option<Self> {
+                parse_ratio(words).map(|ratio| $name { ratio })
+            }
+
+            /// Get the word-based representation of the layout's parameters.
+            pub fn to_words(&self) -> Vec<String> {
+                vec![self.ratio.to_string()]
+            }
+        }
+
+        impl Layout for $name {
+            fn compute_geo(&self, tree: &TagTree, screen: &Screen, sizes: &mut WindowSizes) {
+                arrange(tree, *screen.geometry(), self.ratio, $spiral, sizes);
+            }
+
+            flat_tree_methods!();
+
+            /// New containers take part of the area left by the last client.
+            fn insert_container(&self, tree: &mut TagTree, id: ContainerId) {
+                let root = tree.root();
+                tree.append_child(root, id);
+            }
+
+            fn container_by_direction(&self,
+                                      tree: &TagTree,
+                                      screen: &Screen,
+                                      id: ContainerId,
+                                      direction: Direction) -> Option<ContainerId> {
+                flat_container_by_direction(self, tree, screen, id, direction)
+            }
+
+            /// Accept ratio messages.
+            fn accept_msg(&mut self, msg: Message) -> bool {
+                apply_ratio(&mut self.ratio, msg)
+            }
+        }
+    }
+}
+
+declare_fibonacci_layout!(
+    /// A layout placing clients in a spiral, each taking part of the area left by the previous.
+    Spiral; true);
+
+declare_fibonacci_layout!(
+    /// A layout placing clients towards the lower right corner, each taking part of the area
+    /// left by the previous.
+    Dwindle; false);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wm::testing::*;
+    use wm::tree::ClientId;
+
+    #[test]
+    fn parse_and_apply_ratio() {
+        assert_eq!(parse_ratio(&[]), Some(DEFAULT_RATIO));
+        assert_eq!(parse_ratio(&["30"]), Some(30));
+        assert_eq!(parse_ratio(&["101"]), None);
+        assert_eq!(parse_ratio(&["30", "40"]), None);
+
+        let mut ratio = 50;
+        assert!(apply_ratio(&mut ratio, Message::RatioMessage(RatioMessage::Increase(60))));
+        assert_eq!(ratio, 100);
+        assert!(apply_ratio(&mut ratio, Message::RatioMessage(RatioMessage::Absolute(20))));
+        assert_eq!(ratio, 20);
+        assert!(apply_ratio(&mut ratio, Message::RatioMessage(RatioMessage::Decrease(30))));
+        assert_eq!(ratio, 0);
+    }
+
+    #[test]
+    fn dwindle_compute_geo() {
+        let layout = Dwindle::default();
+        let (tree, _) = layout_tree(&layout);
+
+        let sizes = compute(&layout, &tree);
+        assert_eq!(sizes[&ClientId(1)], Geometry { x: 0, y: 0, width: 60, height: 90 });
+        assert_eq!(sizes[&ClientId(2)], Geometry { x: 60, y: 0, width: 60, height: 45 });
+        assert_eq!(sizes[&ClientId(3)], Geometry { x: 60, y: 45, width: 60, height: 45 });
+    }
+
+    #[test]
+    fn spiral_compute_geo() {
+        let layout = Spiral::default();
+        let (mut tree, _) = layout_tree(&layout);
+        let d = leaf(&mut tree, 4);
+        layout.insert_container(&mut tree, d);
+
+        let sizes = compute(&layout, &tree);
+        assert_eq!(sizes[&ClientId(1)], Geometry { x: 0, y: 0, width: 60, height: 90 });
+        assert_eq!(sizes[&ClientId(2)], Geometry { x: 60, y: 0, width: 60, height: 45 });
+        assert_eq!(sizes[&ClientId(3)], Geometry { x: 90, y: 45, width: 30, height: 45 });
+        assert_eq!(sizes[&ClientId(4)], Geometry { x: 60, y: 45, width: 30, height: 45 });
+    }
+}
@@ -0,0 +1,184 @@
+/*
+ * Copyright Inokentiy Babushkin and contributors (c) 2016-2017
+ *
+ * All rights reserved.
+
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions
+ * are met:
+ *
+ *     * Redistributions of source code must retain the above copyright
+ *       notice, this list of conditions and the following disclaimer.
+ *
+ *     * Redistributions in binary form must reproduce the above
+ *       copyright notice, this list of conditions and the following
+ *       disclaimer in the documentation and/or other materials provided
+ *       with the distribution.
+ *
+ *     * Neither the name of Inokentiy Babushkin nor the names of other
+ *       contributors may be used to endorse or promote products derived
+ *       from this software without specific prior written permission.
+
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+ * "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+ * LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+ * A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+ * OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+ * LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+ * DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+ * THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+use wm::layout::{flat_container_by_direction, tiled_clients, Layout};
+use wm::msg::{GridMessage, Message};
+use wm::tree::{ContainerId, Direction, Screen, TagTree, WindowSizes};
+
+/// A layout arranging clients in a near-square grid of equally sized cells.
+///
+/// The tree is kept flat, holding all clients as children of the root in order. Clients fill
+/// the grid line by line, and the clients in the last line share all of its space.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Grid {
+    /// Whether to lay out clients in rows with more columns than rows, or the other way round.
+    columns: bool,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Grid { columns: true }
+    }
+}
+
+impl Grid {
+    /// Parse the layout from its list of parameters, an optional orientation preference.
+    pub fn parse_from_words(words: &[&str]) -> Option<Self> {
+        match words {
+            [] | ["columns"] => Some(Grid { columns: true }),
+            ["rows"] => Some(Grid { columns: false }),
+            _ => None,
+        }
+    }
+
+    /// Get the word-based representation of the layout's parameters.
+    pub fn to_words(&self) -> Vec<String> {
+        vec![if self.columns { "columns" } else { "rows" }.to_owned()]
+    }
+}
+
+impl Layout for Grid {
+    /// Place clients in lines of at most the rounded-up square root of their number.
+    ///
+    /// Lines are rows if columns are preferred, and columns otherwise.
+    fn compute_geo(&self, tree: &TagTree, screen: &Screen, sizes: &mut WindowSizes) {
+        let clients = tiled_clients(tree);
+        if clients.is_empty() {
+            return;
+        }
+
+        let mut per_line = 1;
+        while per_line * per_line < clients.len() {
+            per_line += 1;
+        }
+
+        let line_count = (clients.len() + per_line - 1) / per_line;
+        let geo = screen.geometry();
+        let lines = if self.columns {
+            geo.split_vertical(&vec![1; line_count])
+        } else {
+            geo.split_horizontal(&vec![1; line_count])
+        };
+
+        for (line, line_geo) in clients.chunks(per_line).zip(lines) {
+            let cells = if self.columns {
+                line_geo.split_horizontal(&vec![1; line.len()])
+            } else {
+                line_geo.split_vertical(&vec![1; line.len()])
+            };
+
+            for (&(client, _), cell) in line.iter().zip(cells) {
+                sizes.insert(client, cell);
+            }
+        }
+    }
+
+    flat_tree_methods!();
+
+    /// New containers are placed in the last cell.
+    fn insert_container(&self, tree: &mut TagTree, id: ContainerId) {
+        let root = tree.root();
+        tree.append_child(root, id);
+    }
+
+    /// Move between neighbouring cells, or cycle through the clients in order.
+    fn container_by_direction(&self,
+                              tree: &TagTree,
+                              screen: &Screen,
+                              id: ContainerId,
+                              direction: Direction) -> Option<ContainerId> {
+        flat_container_by_direction(self, tree, screen, id, direction)
+    }
+
+    /// Accept grid messages changing the orientation preference.
+    fn accept_msg(&mut self, msg: Message) -> bool {
+        match msg {
+            Message::GridMessage(msg) => {
+                self.columns = match msg {
+                    GridMessage::Columns => true,
+                    GridMessage::Rows => false,
+                    GridMessage::Toggle => !self.columns,
+                };
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wm::msg::RatioMessage;
+    use wm::testing::*;
+    use wm::tree::{ClientId, Geometry};
+
+    #[test]
+    fn parse_orientation() {
+        assert_eq!(Grid::parse_from_words(&[]), Some(Grid { columns: true }));
+        assert_eq!(Grid::parse_from_words(&["rows"]), Some(Grid { columns: false }));
+        assert_eq!(Grid::parse_from_words(&["rows", "columns"]), None);
+        assert_eq!(Grid { columns: false }.to_words(), vec!["rows"]);
+    }
+
+    #[test]
+    fn grid_compute_geo() {
+        let mut layout = Grid::default();
+        let (tree, [a, b, c]) = layout_tree(&layout);
+        assert_eq!(tree.get_split(tree.root()).unwrap().children(), &[a, b, c]);
+
+        let sizes = compute(&layout, &tree);
+        assert_eq!(sizes[&ClientId(1)], Geometry { x: 0, y: 0, width: 60, height: 45 });
+        assert_eq!(sizes[&ClientId(2)], Geometry { x: 60, y: 0, width: 60, height: 45 });
+        assert_eq!(sizes[&ClientId(3)], Geometry { x: 0, y: 45, width: 120, height: 45 });
+
+        assert!(layout.accept_msg(Message::GridMessage(GridMessage::Toggle)));
+        let sizes = compute(&layout, &tree);
+        assert_eq!(sizes[&ClientId(1)], Geometry { x: 0, y: 0, width: 60, height: 45 });
+        assert_eq!(sizes[&ClientId(2)], Geometry { x: 0, y: 45, width: 60, height: 45 });
+        assert_eq!(sizes[&ClientId(3)], Geometry { x: 60, y: 0, width: 60, height: 90 });
+    }
+
+    #[test]
+    fn grid_messages() {
+        let mut layout = Grid::default();
+
+        assert!(layout.accept_msg(Message::GridMessage(GridMessage::Rows)));
+        assert_eq!(layout, Grid { columns: false });
+        assert!(layout.accept_msg(Message::GridMessage(GridMessage::Columns)));
+        assert_eq!(layout, Grid { columns: true });
+        assert!(!layout.accept_msg(Message::RatioMessage(RatioMessage::Absolute(50))));
+    }
+}
@@ -0,0 +1,342 @@
+/*
+ * Copyright Inokentiy Babushkin and contributors (c) 2016-2017
+ *
+ * All rights reserved.
+
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions
+ * are met:
+ *
+ *     * Redistributions of source code must retain the above copyright
+ *       notice, this list of conditions and the following disclaimer.
+ *
+ *     * Redistributions in binary form must reproduce the above
+ *       copyright notice, this list of conditions and the following
+ *       disclaimer in the documentation and/or other materials provided
+ *       with the distribution.
+ *
+ *     * Neither the name of Inokentiy Babushkin nor the names of other
+ *       contributors may be used to endorse or promote products derived
+ *       from this software without specific prior written permission.
+
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+ * "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+ * LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+ * A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+ * OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+ * LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+ * DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+ * THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+use std::cmp::min;
+use std::str::FromStr;
+
+use wm::layout::{fill, flat_container_by_direction, tiled_clients, Layout};
+use wm::msg::{MasterFactorMessage, MasterNumberMessage, Message};
+use wm::tree::{ContainerId, Direction, Geometry, Screen, SplitType, TagTree, WindowSizes};
+
+/// The parameters shared by all layouts placing a number of master windows in a separate area.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct MasterParams {
+    /// The percentage of the screen given to the master area, if there is a stack.
+    factor: u8,
+    /// The maximal number of clients in the master area, at least 1.
+    number: u8,
+}
+
+impl Default for MasterParams {
+    fn default() -> Self {
+        MasterParams {
+            factor: 55,
+            number: 1,
+        }
+    }
+}
+
+impl MasterParams {
+    /// Parse the parameters from a list of words, either empty or holding factor and number.
+    fn parse_from_words(words: &[&str]) -> Option<Self> {
+        match words {
+            [] => Some(MasterParams::default()),
+            [factor, number] => {
+                let factor = u8::from_str(factor).ok()?;
+                let number = u8::from_str(number).ok()?;
+
+                if factor <= 100 && number > 0 {
+                    Some(MasterParams { factor, number })
+                } else {
+                    None
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// Get the word-based representation of the parameters.
+    fn to_words(&self) -> Vec<String> {
+        vec![self.factor.to_string(), self.number.to_string()]
+    }
+
+    /// Get the number of masters among the given number of clients.
+    pub fn masters(&self, clients: usize) -> usize {
+        min(usize::from(self.number), clients)
+    }
+
+    /// Split an area into the master and the stack area, in that order.
+    ///
+    /// The areas are placed along the split type, the master area first unless reversed. The
+    /// factor is kept away from the extremes, so that neither area collapses entirely.
+    fn split(&self, geo: Geometry, split_type: SplitType, reversed: bool)
+        -> (Geometry, Geometry)
+    {
+        let factor = u32::from(self.factor.clamp(1, 99));
+        let weights = if reversed { [100 - factor, factor] } else { [factor, 100 - factor] };
+        let geos = match split_type {
+            SplitType::Horizontal => geo.split_horizontal(&weights),
+            _ => geo.split_vertical(&weights),
+        };
+
+        if reversed { (geos[1], geos[0]) } else { (geos[0], geos[1]) }
+    }
+
+    /// Compute the geometries of the tiled clients in a flat tree.
+    ///
+    /// The first clients are placed in the master area, all others in the stack area, if there
+    /// are any. Each area lines up its clients across the split type.
+    fn arrange(&self,
+               tree: &TagTree,
+               geo: Geometry,
+               sizes: &mut WindowSizes,
+               split_type: SplitType,
+               reversed: bool) {
+        let clients = tiled_clients(tree);
+        let (masters, stack) = clients.split_at(self.masters(clients.len()));
+        let across = match split_type {
+            SplitType::Horizontal => SplitType::Vertical,
+            _ => SplitType::Horizontal,
+        };
+
+        if stack.is_empty() {
+            fill(masters, geo, across, sizes);
+        } else {
+            let (master_geo, stack_geo) = self.split(geo, split_type, reversed);
+            fill(masters, master_geo, across, sizes);
+            fill(stack, stack_geo, across, sizes);
+        }
+    }
+
+    /// Compute the geometries of the tiled clients in a flat tree, masters in the centre.
+    ///
+    /// The stack clients are distributed alternately to columns on the right and on the left of
+    /// the master column. A single stack client is placed on the right, leaving the master
+    /// column on the left.
+    fn arrange_centred(&self, tree: &TagTree, geo: Geometry, sizes: &mut WindowSizes) {
+        let clients = tiled_clients(tree);
+        let (masters, stack) = clients.split_at(self.masters(clients.len()));
+
+        if stack.len() < 2 {
+            return self.arrange(tree, geo, sizes, SplitType::Horizontal, false);
+        }
+
+        let factor = u32::from(self.factor.clamp(1, 99));
+        let columns = geo.split_horizontal(&[100 - factor, 2 * factor, 100 - factor]);
+        let right: Vec<_> = stack.iter().cloned().step_by(2).collect();
+        let left: Vec<_> = stack.iter().cloned().skip(1).step_by(2).collect();
+
+        fill(&left, columns[0], SplitType::Vertical, sizes);
+        fill(masters, columns[1], SplitType::Vertical, sizes);
+        fill(&right, columns[2], SplitType::Vertical, sizes);
+    }
+
+    /// Apply a master factor or master number message.
+    pub fn accept_msg(&mut self, msg: Message) -> bool {
+        match msg {
+            Message::MasterFactorMessage(msg) => {
+                self.factor = match msg {
+                    MasterFactorMessage::Absolute(f) => min(f, 100),
+                    MasterFactorMessage::Increase(f) => min(self.factor.saturating_add(f), 100),
+                    MasterFactorMessage::Decrease(f) => self.factor.saturating_sub(f),
+                };
+                true
+            },
+            Message::MasterNumberMessage(msg) => {
+                self.number = match msg {
+                    MasterNumberMessage::Absolute(n) => n,
+                    MasterNumberMessage::Increase(n) => self.number.saturating_add(n),
+                    MasterNumberMessage::Decrease(n) => self.number.saturating_sub(n),
+                }.max(1);
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Declare a layout holding a flat tree, split into a master and a stack area.
+///
+/// The geometries are computed by the given method of the layout's parameters, passed the
+/// given arguments after the tree, the screen's geometry and the output map.
+macro_rules! declare_master_layout {
+    ($(#[$attr:meta])* $name:ident; $arrange:ident($($arg:expr),*)) => {
+        $(#[$attr])*
+        #[derive(PartialEq, Eq, Clone, Debug, Default)]
+        pub struct $name {
+            params: MasterParams,
+        }
+
+        impl $name {
+            /// Parse the layout from its list of parameters.
+            pub fn parse_from_words(words: &[&str]) -> Option<Self> {
+                MasterParams::parse_from_words(words).map(|params| $name { params })
+            }
+
+            /// Get the word-based representation of the layout's parameters.
+            pub fn to_words(&self) -> Vec<String> {
+                self.params.to_words()
+            }
+        }
+
+        impl Layout for $name {
+            fn compute_geo(&self, tree: &TagTree, screen: &Screen, sizes: &mut WindowSizes) {
+                self.params.$arrange(tree, *screen.geometry(), sizes $(, $arg)*);
+            }
+
+            flat_tree_methods!();
+
+            /// New containers become the first master.
+            fn insert_container(&self, tree: &mut TagTree, id: ContainerId) {
+                let root = tree.root();
+                tree.attach_at(root, 0, id);
+            }
+
+            fn container_by_direction(&self,
+                                      tree: &TagTree,
+                                      screen: &Screen,
+                                      id: ContainerId,
+                                      direction: Direction) -> Option<ContainerId> {
+                flat_container_by_direction(self, tree, screen, id, direction)
+            }
+
+            fn accept_msg(&mut self, msg: Message) -> bool {
+                self.params.accept_msg(msg)
+            }
+        }
+    }
+}
+
+declare_master_layout!(
+    /// A dwm-style tiling layout with a master area on the left and a stack on the right.
+    ///
+    /// The tree is kept flat, holding all clients as children of the root in order. The first
+    /// clients are masters, all others form the stack.
+    Master; arrange(SplitType::Horizontal, false));
+
+declare_master_layout!(
+    /// A tiling layout with a master area on top and a stack spread horizontally below.
+    BottomStack; arrange(SplitType::Vertical, false));
+
+declare_master_layout!(
+    /// A tiling layout with a master area at the bottom and a stack spread horizontally above.
+    TopStack; arrange(SplitType::Vertical, true));
+
+declare_master_layout!(
+    /// A tiling layout with a master area in the centre and stack columns on both sides.
+    ///
+    /// Without any stack clients, the masters take the whole width.
+    CentredMaster; arrange_centred());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wm::testing::*;
+    use wm::tree::{ClientId, Direction};
+
+    #[test]
+    fn parse_params() {
+        assert_eq!(MasterParams::parse_from_words(&[]), Some(MasterParams::default()));
+        assert_eq!(MasterParams::parse_from_words(&["60", "2"]),
+                   Some(MasterParams { factor: 60, number: 2 }));
+        assert_eq!(MasterParams::parse_from_words(&["101", "1"]), None);
+        assert_eq!(MasterParams::parse_from_words(&["50", "0"]), None);
+    }
+
+    #[test]
+    fn master_compute_geo() {
+        let layout = Master::default();
+        let (tree, [a, b, c]) = layout_tree(&layout);
+        assert_eq!(tree.get_split(tree.root()).unwrap().children(), &[c, b, a]);
+
+        let sizes = compute(&layout, &tree);
+        assert_eq!(sizes[&ClientId(3)], Geometry { x: 0, y: 0, width: 66, height: 90 });
+        assert_eq!(sizes[&ClientId(2)], Geometry { x: 66, y: 0, width: 54, height: 45 });
+        assert_eq!(sizes[&ClientId(1)], Geometry { x: 66, y: 45, width: 54, height: 45 });
+    }
+
+    #[test]
+    fn bottom_and_top_stack_compute_geo() {
+        let layout = BottomStack::default();
+        let (tree, _) = layout_tree(&layout);
+
+        let sizes = compute(&layout, &tree);
+        assert_eq!(sizes[&ClientId(3)], Geometry { x: 0, y: 0, width: 120, height: 49 });
+        assert_eq!(sizes[&ClientId(2)], Geometry { x: 0, y: 49, width: 60, height: 41 });
+        assert_eq!(sizes[&ClientId(1)], Geometry { x: 60, y: 49, width: 60, height: 41 });
+
+        let layout = TopStack::default();
+        let (tree, _) = layout_tree(&layout);
+
+        let sizes = compute(&layout, &tree);
+        assert_eq!(sizes[&ClientId(3)], Geometry { x: 0, y: 40, width: 120, height: 50 });
+        assert_eq!(sizes[&ClientId(2)], Geometry { x: 0, y: 0, width: 60, height: 40 });
+        assert_eq!(sizes[&ClientId(1)], Geometry { x: 60, y: 0, width: 60, height: 40 });
+    }
+
+    #[test]
+    fn centred_master_compute_geo() {
+        let layout = CentredMaster::default();
+        let (mut tree, [a, _, _]) = layout_tree(&layout);
+
+        let sizes = compute(&layout, &tree);
+        assert_eq!(sizes[&ClientId(1)], Geometry { x: 0, y: 0, width: 27, height: 90 });
+        assert_eq!(sizes[&ClientId(3)], Geometry { x: 27, y: 0, width: 66, height: 90 });
+        assert_eq!(sizes[&ClientId(2)], Geometry { x: 93, y: 0, width: 27, height: 90 });
+
+        layout.delete_container(&mut tree, a);
+        let sizes = compute(&layout, &tree);
+        assert_eq!(sizes[&ClientId(3)], Geometry { x: 0, y: 0, width: 66, height: 90 });
+        assert_eq!(sizes[&ClientId(2)], Geometry { x: 66, y: 0, width: 54, height: 90 });
+    }
+
+    #[test]
+    fn master_messages() {
+        let mut params = MasterParams::default();
+
+        let msg = Message::MasterFactorMessage(MasterFactorMessage::Increase(60));
+        assert!(params.accept_msg(msg));
+        assert_eq!(params.factor, 100);
+        let msg = Message::MasterNumberMessage(MasterNumberMessage::Decrease(5));
+        assert!(params.accept_msg(msg));
+        assert_eq!(params.number, 1);
+        assert_eq!(params.masters(0), 0);
+    }
+
+    #[test]
+    fn master_container_by_direction() {
+        let layout = Master::default();
+        let (tree, [a, b, c]) = layout_tree(&layout);
+        let screen = screen();
+        let neighbour =
+            |id, direction| layout.container_by_direction(&tree, &screen, id, direction);
+
+        assert_eq!(neighbour(a, Direction::Up), Some(b));
+        assert_eq!(neighbour(a, Direction::Left), Some(c));
+        assert_eq!(neighbour(c, Direction::Left), None);
+        assert_eq!(neighbour(c, Direction::InOrderForward), Some(b));
+        assert_eq!(neighbour(a, Direction::InOrderForward), Some(c));
+    }
+}
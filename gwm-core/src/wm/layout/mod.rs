@@ -32,7 +32,38 @@
  * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
  */
 
-use wm::tree::{ContainerId, Direction, Screen, TagTree, WindowSizes};
+/// Implement the `Layout` methods shared by all layouts keeping a flat tree.
+///
+/// To be invoked inside an `impl Layout` block, with `TagTree` and `ContainerId` in scope.
+macro_rules! flat_tree_methods {
+    () => {
+        /// A tree is valid if all children of the root are clients.
+        fn check_tree(&self, tree: &TagTree) -> bool {
+            tree.is_flat()
+        }
+
+        /// Flatten the tree, keeping the clients in order.
+        fn correct_tree(&self, tree: &mut TagTree) {
+            tree.flatten();
+        }
+
+        fn delete_container(&self, tree: &mut TagTree, id: ContainerId) {
+            tree.detach(id);
+        }
+    }
+}
+
+pub mod fibonacci;
+pub mod grid;
+pub mod master;
+pub mod monocle;
+
+use wm::layout::fibonacci::{Dwindle, Spiral};
+use wm::layout::grid::Grid;
+use wm::layout::master::{BottomStack, CentredMaster, Master, TopStack};
+use wm::layout::monocle::Monocle;
+use wm::tree::{ClientContainer, ClientId, Container, ContainerId, Direction, Geometry, Screen,
+               SplitType, TagTree, WindowSizes};
 use wm::msg::Message;
 
 /// Layout trait.
@@ -58,13 +89,17 @@ pub trait Layout {
     fn correct_tree(&self, &mut TagTree);
     /// Insert a new container into the tree.
     ///
+    /// The container has already been allocated in the tree, but isn't attached to any parent.
+    ///
     /// NB: since the container might be essentially an arbitrary subtree, it is not guaranteed
     /// that the tree will be layout-consistent after insertion. This is *allowed*, because a
     /// call to `correct_tree` will be issued from outside.
-
     fn insert_container(&self, &mut TagTree, ContainerId);
+
     /// Delete a container from the tree.
     ///
+    /// The container is detached from it's parent, but is freed by the caller afterwards.
+    ///
     /// NB: since the container might be essentially an arbitrary subtree, it is not guaranteed
     /// that the tree will be layout-consistent after deletion. This is *allowed*, because a
     /// call to `correct_tree` will be issued from outside.
@@ -72,23 +107,92 @@ pub trait Layout {
 
     /// Get a container by direction.
     ///
-    /// Used to compute focus and container swapping. In some cases, this transfers the tree in a
+    /// Used to compute focus and container swapping, on the screen the tree is shown on. In
+    /// some cases, this transfers the tree in a
     /// non-layout-consistent state. A call to `correct_tree` is then issued.
-    fn container_by_direction(&self, &TagTree, ContainerId, Direction) -> Option<ContainerId>;
+    fn container_by_direction(&self, &TagTree, &Screen, ContainerId, Direction)
+        -> Option<ContainerId>;
 
     /// Accept a message and signifty whether it was accepted.
     ///
     /// Returning `false` implies no change to the layout's state has been performed.
     fn accept_msg(&mut self, Message) -> bool;
+
+    /// Get a short description of the layout's state, to be shown by a status bar.
+    fn status(&self, _: &TagTree) -> Option<String> {
+        None
+    }
 }
 
-#[derive(PartialEq, Eq, Clone)]
+#[derive(PartialEq, Eq, Clone, Debug)]
 pub struct Manual;
 
+impl Manual {
+    /// Parse the layout from it's (empty) list of parameters.
+    fn parse_from_words(words: &[&str]) -> Option<Self> {
+        if words.is_empty() {
+            Some(Manual)
+        } else {
+            None
+        }
+    }
+
+    /// Recursively compute the geometries of the clients in a subtree, given its geometry.
+    ///
+    /// Floating containers are skipped and don't take up any space.
+    fn arrange(tree: &TagTree, id: ContainerId, geo: Geometry, sizes: &mut WindowSizes) {
+        let container = match tree.get(id) {
+            Some(container) => container,
+            None => return,
+        };
+
+        match *container {
+            Container::Split(ref split) => {
+                let (tiled, weights): (Vec<ContainerId>, Vec<u32>) = split
+                    .children()
+                    .iter()
+                    .cloned()
+                    .zip(split.weights().iter().cloned())
+                    .filter(|&(c, _)| tree.get(c).map(|c| !c.is_floating()).unwrap_or(false))
+                    .unzip();
+
+                let geos = match split.split_type() {
+                    SplitType::Horizontal => geo.split_horizontal(&weights),
+                    SplitType::Vertical => geo.split_vertical(&weights),
+                    SplitType::Tabbed => {
+                        // only the last-focused child is shown, all others remain unmapped
+                        let shown = split
+                            .last_focused()
+                            .filter(|c| tiled.contains(c))
+                            .or_else(|| tiled.first().cloned());
+
+                        if let Some(child) = shown {
+                            Manual::arrange(tree, child, geo, sizes);
+                        }
+
+                        return;
+                    },
+                };
+
+                for (child, child_geo) in tiled.into_iter().zip(geos) {
+                    Manual::arrange(tree, child, child_geo, sizes);
+                }
+            },
+            Container::Client(ref client) => {
+                sizes.insert(client.client(), geo);
+            },
+        }
+    }
+}
+
 impl Layout for Manual {
     /// Compute the geometries in a standard fashion.
-    fn compute_geo(&self, _: &TagTree, _: &Screen, _: &mut WindowSizes) {
-        // TODO: implement
+    ///
+    /// Split containers divide their area among their children according to their split type,
+    /// proportionally to the children's weights. Tabbed containers give their whole area to the
+    /// last-focused child.
+    fn compute_geo(&self, tree: &TagTree, screen: &Screen, sizes: &mut WindowSizes) {
+        Manual::arrange(tree, tree.root(), *screen.geometry(), sizes);
     }
 
     /// The manual layout considers any tree valid.
@@ -97,22 +201,99 @@ impl Layout for Manual {
     /// No correction is performed, ever.
     fn correct_tree(&self, _: &mut TagTree) { }
 
-    /// Insert a container.
-    fn insert_container(&self, _: &mut TagTree, _: ContainerId) {
-        // TODO: implement
+    /// Insert a container right after the focused container, or at the end of the root
+    /// container if that is focused or no container is focused.
+    fn insert_container(&self, tree: &mut TagTree, id: ContainerId) {
+        let attached = match tree.focused() {
+            Some(focused) if focused != id => tree.attach_after(id, focused),
+            _ => false,
+        };
+
+        if !attached {
+            let root = tree.root();
+            tree.append_child(root, id);
+        }
     }
 
-    /// Remove a container.
-    fn delete_container(&self, _: &mut TagTree, _: ContainerId) {
-        // TODO: implement
+    /// Remove a container, freeing all split containers left empty, except for the root.
+    fn delete_container(&self, tree: &mut TagTree, id: ContainerId) {
+        let mut parent = tree.parent(id);
+        tree.detach(id);
+
+        while let Some(p) = parent {
+            let empty = tree.get_split(p).map(|s| s.children().is_empty()).unwrap_or(false);
+
+            if p == tree.root() || !empty {
+                break;
+            }
+
+            parent = tree.parent(p);
+            tree.free(p);
+        }
     }
 
     /// Get a container by direction.
-    fn container_by_direction(&self, _: &TagTree, _: ContainerId, _: Direction)
-        -> Option<ContainerId>
-    {
-        // TODO: implement
-        None
+    ///
+    /// Geometric directions move to the neighbour in the closest ancestor split along the
+    /// direction, entering it at it's last-focused leaf. Tabbed containers count as horizontal
+    /// splits. All other directions cycle through the leaves, the containers in pre-order, or
+    /// the siblings of the container, respectively.
+    fn container_by_direction(&self,
+                              tree: &TagTree,
+                              _: &Screen,
+                              id: ContainerId,
+                              direction: Direction) -> Option<ContainerId> {
+        match direction {
+            Direction::Left | Direction::Up | Direction::Right | Direction::Down => {
+                let forward = direction == Direction::Right || direction == Direction::Down;
+                let split_type = direction.split_type();
+                let mut child = id;
+
+                while let Some(parent) = tree.parent(child) {
+                    let split = tree.get_split(parent).unwrap();
+                    let along = match split.split_type() {
+                        SplitType::Tabbed => split_type == Some(SplitType::Horizontal),
+                        t => split_type == Some(t),
+                    };
+
+                    if along {
+                        let tiled: Vec<ContainerId> = split
+                            .children()
+                            .iter()
+                            .cloned()
+                            .filter(|&c| c == child || !is_floating(tree, c))
+                            .collect();
+                        let index = tiled.iter().position(|&c| c == child).unwrap();
+                        let neighbour = if forward {
+                            tiled.get(index + 1)
+                        } else {
+                            index.checked_sub(1).and_then(|i| tiled.get(i))
+                        };
+
+                        if let Some(&neighbour) = neighbour {
+                            return Some(tree.focused_leaf(neighbour));
+                        }
+                    }
+
+                    child = parent;
+                }
+
+                None
+            },
+            Direction::InOrderForward | Direction::InOrderBackward => {
+                let forward = direction == Direction::InOrderForward;
+                cycle(&tree.leaves(tree.root()), tree.focused_leaf(id), forward)
+            },
+            Direction::PreOrderForward | Direction::PreOrderBackward => {
+                let forward = direction == Direction::PreOrderForward;
+                cycle(&tree.preorder(tree.root())[1..], id, forward)
+            },
+            Direction::SiblingCycleForward | Direction::SiblingCycleBackward => {
+                let forward = direction == Direction::SiblingCycleForward;
+                let parent = tree.parent(id)?;
+                cycle(tree.get_split(parent)?.children(), id, forward)
+            },
+        }
     }
 
     /// Drop all messages.
@@ -121,15 +302,164 @@ impl Layout for Manual {
     }
 }
 
-/// The enum holding all possible layouts, and a macro to match on it.
-declare_hierarchy!(LayoutContainer; match_layout, Manual);
+/// Prepend a layout's name to the words representing it's parameters.
+fn prepend(name: &str, mut words: Vec<String>) -> Vec<String> {
+    words.insert(0, name.to_owned());
+    words
+}
+
+/// Check whether a container is marked floating.
+fn is_floating(tree: &TagTree, id: ContainerId) -> bool {
+    tree.get(id).map(Container::is_floating).unwrap_or(false)
+}
+
+/// Get the container following or preceding a container in a list, wrapping around.
+///
+/// Returns `None` if the container isn't present, or is the only container in the list.
+fn cycle(containers: &[ContainerId], id: ContainerId, forward: bool) -> Option<ContainerId> {
+    let n = containers.len();
+    let index = containers.iter().position(|&c| c == id)?;
+
+    if n < 2 {
+        None
+    } else if forward {
+        Some(containers[(index + 1) % n])
+    } else {
+        Some(containers[(index + n - 1) % n])
+    }
+}
+
+/// Get the tiled clients in a flat tree, along with their weights, in order.
+fn tiled_clients(tree: &TagTree) -> Vec<(ClientId, u32)> {
+    let root = tree.get_split(tree.root()).unwrap();
+
+    root.children()
+        .iter()
+        .zip(root.weights())
+        .filter_map(|(&c, &w)| {
+            let container = tree.get(c)?;
+
+            if container.is_floating() {
+                None
+            } else {
+                container.as_client().map(|c| (c.client(), w))
+            }
+        })
+        .collect()
+}
+
+/// Split a rectangle among clients, proportionally to their weights.
+fn fill(clients: &[(ClientId, u32)],
+        geo: Geometry,
+        split_type: SplitType,
+        sizes: &mut WindowSizes) {
+    let weights: Vec<u32> = clients.iter().map(|&(_, w)| w).collect();
+    let geos = match split_type {
+        SplitType::Horizontal => geo.split_horizontal(&weights),
+        _ => geo.split_vertical(&weights),
+    };
+
+    for (&(client, _), geo) in clients.iter().zip(geos) {
+        sizes.insert(client, geo);
+    }
+}
+
+/// Get a container by direction in a flat tree, using the geometries computed by a layout.
+///
+/// Geometric directions pick the closest client lying entirely in that direction, when
+/// arranged on the given screen. All other directions cycle through the children of the root.
+fn flat_container_by_direction<L: Layout>(layout: &L,
+                                          tree: &TagTree,
+                                          screen: &Screen,
+                                          id: ContainerId,
+                                          direction: Direction) -> Option<ContainerId> {
+    let children = tree.get_split(tree.root())?.children();
+
+    if direction.split_type().is_none() {
+        let forward = direction == Direction::InOrderForward ||
+            direction == Direction::PreOrderForward ||
+            direction == Direction::SiblingCycleForward;
+
+        return cycle(children, id, forward);
+    }
+
+    let mut sizes = WindowSizes::new();
+    layout.compute_geo(tree, screen, &mut sizes);
+
+    let geometry = |c: ContainerId| {
+        tree.get(c)
+            .and_then(Container::as_client)
+            .map(ClientContainer::client)
+            .and_then(|c| sizes.get(&c))
+    };
+    let centre = |g: &Geometry| {
+        (i64::from(g.x) + i64::from(g.width) / 2, i64::from(g.y) + i64::from(g.height) / 2)
+    };
+
+    let source = geometry(id)?;
+    let (x, y) = centre(source);
+
+    children
+        .iter()
+        .cloned()
+        .filter(|&c| c != id)
+        .filter_map(|c| {
+            let geo = geometry(c)?;
+            let (cx, cy) = centre(geo);
+
+            // the distance along the direction, and the offset of the centres across it
+            let (distance, offset) = match direction {
+                Direction::Left => (i64::from(source.x) - i64::from(geo.x + geo.width), cy - y),
+                Direction::Right => (i64::from(geo.x) - i64::from(source.x + source.width), cy - y),
+                Direction::Up => (i64::from(source.y) - i64::from(geo.y + geo.height), cx - x),
+                _ => (i64::from(geo.y) - i64::from(source.y + source.height), cx - x),
+            };
+
+            if distance >= 0 { Some(((distance, offset.abs()), c)) } else { None }
+        })
+        .min_by_key(|&(key, _)| key)
+        .map(|(_, c)| c)
+}
+
+/// The enum holding all possible layouts, a macro to match on it, and a parser to construct
+/// layouts by name.
+declare_hierarchy_with_parser!(#[derive(Clone)] LayoutContainer; match_layout,
+                               (Manual; "manual"),
+                               (Master; "master"),
+                               (BottomStack; "bstack"),
+                               (TopStack; "tstack"),
+                               (Grid; "grid"),
+                               (Monocle; "monocle"),
+                               (Spiral; "spiral"),
+                               (Dwindle; "dwindle"),
+                               (CentredMaster; "centred"));
 
 impl LayoutContainer {
+    /// Get the word-based representation of the layout, as accepted by `parse_from_words`.
+    pub fn to_words(&self) -> Vec<String> {
+        match *self {
+            LayoutContainer::Manual(_) => vec!["manual".to_owned()],
+            LayoutContainer::Master(ref l) => prepend("master", l.to_words()),
+            LayoutContainer::BottomStack(ref l) => prepend("bstack", l.to_words()),
+            LayoutContainer::TopStack(ref l) => prepend("tstack", l.to_words()),
+            LayoutContainer::Grid(ref l) => prepend("grid", l.to_words()),
+            LayoutContainer::Monocle(_) => vec!["monocle".to_owned()],
+            LayoutContainer::Spiral(ref l) => prepend("spiral", l.to_words()),
+            LayoutContainer::Dwindle(ref l) => prepend("dwindle", l.to_words()),
+            LayoutContainer::CentredMaster(ref l) => prepend("centred", l.to_words()),
+        }
+    }
+
+    /// Get the name of the layout, as accepted by `parse_from_words`.
+    pub fn name(&self) -> String {
+        self.to_words().swap_remove(0)
+    }
+
     /// Get a reference to a trait object inside the layout enum.
     ///
     /// This has not much practical use in most cases, but it ensures that all types placed in
     /// variants of the layout enum actually implement the `Layout` trait.
-    pub fn as_layout(&self) -> &Layout {
+    pub fn as_layout(&self) -> &dyn Layout {
         match_layout!(*self, ref l => l)
     }
 }
@@ -139,3 +469,184 @@ impl Default for LayoutContainer {
         LayoutContainer::Manual(Manual)
     }
 }
+
+/// An ordered, non-empty list of layouts, one of which is active.
+///
+/// Each layout keeps it's parameters while inactive, so that they are restored when switching
+/// back to it.
+#[derive(Clone, Debug)]
+pub struct LayoutList {
+    /// The layouts in the list.
+    layouts: Vec<LayoutContainer>,
+    /// The index of the active layout.
+    current: usize,
+}
+
+impl Default for LayoutList {
+    fn default() -> Self {
+        LayoutList::new(Vec::new())
+    }
+}
+
+impl LayoutList {
+    /// Construct a list from the given layouts, the first one being active.
+    ///
+    /// An empty list is replaced by a list holding just the default layout.
+    pub fn new(layouts: Vec<LayoutContainer>) -> LayoutList {
+        LayoutList::with_current(layouts, 0)
+    }
+
+    /// Construct a list from the given layouts, the one with the given index being active.
+    pub fn with_current(mut layouts: Vec<LayoutContainer>, current: usize) -> LayoutList {
+        if layouts.is_empty() {
+            layouts.push(LayoutContainer::default());
+        }
+
+        let current = if current < layouts.len() { current } else { 0 };
+
+        LayoutList { layouts, current }
+    }
+
+    /// Get the layouts in the list.
+    pub fn layouts(&self) -> &[LayoutContainer] {
+        &self.layouts
+    }
+
+    /// Get the index of the active layout.
+    pub fn index(&self) -> usize {
+        self.current
+    }
+
+    /// Get the active layout.
+    pub fn current(&self) -> &LayoutContainer {
+        &self.layouts[self.current]
+    }
+
+    /// Get the active layout mutably.
+    pub fn current_mut(&mut self) -> &mut LayoutContainer {
+        &mut self.layouts[self.current]
+    }
+
+    /// Get a reference to a trait object for the active layout.
+    pub fn as_layout(&self) -> &dyn Layout {
+        self.current().as_layout()
+    }
+
+    /// Activate the layout following or preceding the active one, wrapping around.
+    ///
+    /// Returns `false` if the list holds only one layout.
+    pub fn cycle(&mut self, forward: bool) -> bool {
+        let n = self.layouts.len();
+
+        if n < 2 {
+            return false;
+        }
+
+        self.current = if forward { (self.current + 1) % n } else { (self.current + n - 1) % n };
+        true
+    }
+
+    /// Activate the first layout with the given name.
+    pub fn select(&mut self, name: &str) -> bool {
+        match self.layouts.iter().position(|l| l.name() == name) {
+            Some(index) => {
+                self.current = index;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Activate a layout, replacing the first layout of the same name, or inserting it after
+    /// the active one if there is none.
+    pub fn replace(&mut self, layout: LayoutContainer) {
+        let name = layout.name();
+
+        match self.layouts.iter().position(|l| l.name() == name) {
+            Some(index) => {
+                self.layouts[index] = layout;
+                self.current = index;
+            },
+            None => {
+                self.current += 1;
+                self.layouts.insert(self.current, layout);
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wm::testing::*;
+
+    #[test]
+    fn manual_compute_geo() {
+        let (mut tree, _) = nested_tree();
+        let sizes = compute(&Manual, &tree);
+
+        assert_eq!(sizes.len(), 3);
+        assert_eq!(sizes[&ClientId(1)], Geometry { x: 0, y: 0, width: 60, height: 90 });
+        assert_eq!(sizes[&ClientId(2)], Geometry { x: 60, y: 0, width: 60, height: 45 });
+        assert_eq!(sizes[&ClientId(3)], Geometry { x: 60, y: 45, width: 60, height: 45 });
+
+        // no window shrinks to nothing
+        assert!(tree.resize(Direction::Left, 100));
+        let sizes = compute(&Manual, &tree);
+        assert_eq!(sizes[&ClientId(1)], Geometry { x: 0, y: 0, width: 1, height: 90 });
+        assert_eq!(sizes[&ClientId(3)], Geometry { x: 1, y: 45, width: 119, height: 45 });
+    }
+
+    #[test]
+    fn manual_compute_geo_tabbed() {
+        let (mut tree, _) = nested_tree();
+        let root = tree.root();
+        tree.split(root, SplitType::Tabbed);
+        let sizes = compute(&Manual, &tree);
+
+        assert_eq!(sizes.len(), 2);
+        assert_eq!(sizes[&ClientId(2)], Geometry { x: 0, y: 0, width: 120, height: 45 });
+        assert_eq!(sizes[&ClientId(3)], Geometry { x: 0, y: 45, width: 120, height: 45 });
+    }
+
+    #[test]
+    fn manual_container_by_direction() {
+        let (tree, [a, b, c]) = nested_tree();
+        let screen = screen();
+        let neighbour =
+            |id, direction| Manual.container_by_direction(&tree, &screen, id, direction);
+
+        assert_eq!(neighbour(c, Direction::Left), Some(a));
+        assert_eq!(neighbour(c, Direction::Up), Some(b));
+        assert_eq!(neighbour(c, Direction::Right), None);
+        assert_eq!(neighbour(a, Direction::Right), Some(c));
+        assert_eq!(neighbour(a, Direction::InOrderForward), Some(b));
+        assert_eq!(neighbour(c, Direction::InOrderForward), Some(a));
+    }
+
+    #[test]
+    fn layout_list_cycle_and_select() {
+        let names = |list: &LayoutList| list.current().name();
+        let mut list = LayoutList::new(vec![layout("manual"), layout("master"), layout("grid")]);
+
+        assert!(list.cycle(false));
+        assert_eq!(names(&list), "grid");
+        assert!(list.cycle(true));
+        assert_eq!(names(&list), "manual");
+        assert!(list.select("master"));
+        assert_eq!(list.index(), 1);
+        assert!(!list.select("monocle"));
+        assert_eq!(list.index(), 1);
+
+        list.replace(layout("monocle"));
+        assert_eq!(list.index(), 2);
+        assert_eq!(list.layouts().len(), 4);
+        list.replace(layout("master 70 2"));
+        assert_eq!(list.current().to_words(), vec!["master", "70", "2"]);
+
+        let mut single = LayoutList::default();
+        assert!(!single.cycle(true));
+        assert_eq!(names(&single), "manual");
+    }
+}
@@ -0,0 +1,172 @@
+/*
+ * Copyright Inokentiy Babushkin and contributors (c) 2016-2017
+ *
+ * All rights reserved.
+
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions
+ * are met:
+ *
+ *     * Redistributions of source code must retain the above copyright
+ *       notice, this list of conditions and the following disclaimer.
+ *
+ *     * Redistributions in binary form must reproduce the above
+ *       copyright notice, this list of conditions and the following
+ *       disclaimer in the documentation and/or other materials provided
+ *       with the distribution.
+ *
+ *     * Neither the name of Inokentiy Babushkin nor the names of other
+ *       contributors may be used to endorse or promote products derived
+ *       from this software without specific prior written permission.
+
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+ * "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+ * LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+ * A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+ * OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+ * LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+ * DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+ * THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+use wm::layout::{cycle, tiled_clients, Layout};
+use wm::msg::Message;
+use wm::tree::{ClientId, Container, ContainerId, Direction, Screen, TagTree, WindowSizes};
+
+/// A layout giving the whole screen to the focused client, leaving all others unmapped.
+///
+/// The tree is kept flat, holding all clients as children of the root in order.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct Monocle;
+
+impl Monocle {
+    /// Parse the layout from its (empty) list of parameters.
+    pub fn parse_from_words(words: &[&str]) -> Option<Self> {
+        if words.is_empty() {
+            Some(Monocle)
+        } else {
+            None
+        }
+    }
+
+    /// Get the tiled clients in the tree and the index of the one shown.
+    ///
+    /// This is the focused client, or the first one if the focused container is floating.
+    fn shown(tree: &TagTree) -> (Vec<(ClientId, u32)>, usize) {
+        let clients = tiled_clients(tree);
+        let focused = tree
+            .focused()
+            .and_then(|id| tree.get(id))
+            .and_then(Container::as_client)
+            .map(|c| c.client());
+        let index = clients.iter().position(|&(c, _)| Some(c) == focused).unwrap_or(0);
+
+        (clients, index)
+    }
+}
+
+impl Layout for Monocle {
+    fn compute_geo(&self, tree: &TagTree, screen: &Screen, sizes: &mut WindowSizes) {
+        let (clients, index) = Monocle::shown(tree);
+
+        if let Some(&(client, _)) = clients.get(index) {
+            sizes.insert(client, *screen.geometry());
+        }
+    }
+
+    flat_tree_methods!();
+
+    /// New containers are placed right after the focused one.
+    fn insert_container(&self, tree: &mut TagTree, id: ContainerId) {
+        let attached = match tree.focused() {
+            Some(focused) if focused != id => tree.attach_after(id, focused),
+            _ => false,
+        };
+
+        if !attached {
+            let root = tree.root();
+            tree.append_child(root, id);
+        }
+    }
+
+    /// Cycle through the clients in order, right and down moving forward, left and up moving
+    /// backward.
+    fn container_by_direction(&self,
+                              tree: &TagTree,
+                              _: &Screen,
+                              id: ContainerId,
+                              direction: Direction) -> Option<ContainerId> {
+        let forward = match direction {
+            Direction::Right | Direction::Down => true,
+            Direction::Left | Direction::Up => false,
+            d => {
+                d == Direction::InOrderForward ||
+                    d == Direction::PreOrderForward ||
+                    d == Direction::SiblingCycleForward
+            },
+        };
+
+        cycle(tree.get_split(tree.root())?.children(), id, forward)
+    }
+
+    /// Drop all messages.
+    fn accept_msg(&mut self, _: Message) -> bool {
+        false
+    }
+
+    /// Report the position of the shown client among all tiled clients as `[n/m]`.
+    fn status(&self, tree: &TagTree) -> Option<String> {
+        let (clients, index) = Monocle::shown(tree);
+
+        if clients.is_empty() {
+            None
+        } else {
+            Some(format!("[{}/{}]", index + 1, clients.len()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wm::testing::*;
+
+    #[test]
+    fn monocle_shows_focused_client() {
+        let layout = Monocle;
+        let (mut tree, [a, b, c]) = layout_tree(&layout);
+        assert_eq!(tree.get_split(tree.root()).unwrap().children(), &[a, b, c]);
+
+        let sizes = compute(&layout, &tree);
+        assert_eq!(sizes.len(), 1);
+        assert_eq!(sizes[&ClientId(3)], SCREEN);
+        assert_eq!(layout.status(&tree), Some("[3/3]".to_owned()));
+
+        tree.focus(a);
+        let sizes = compute(&layout, &tree);
+        assert_eq!(sizes.len(), 1);
+        assert_eq!(sizes[&ClientId(1)], SCREEN);
+        assert_eq!(layout.status(&tree), Some("[1/3]".to_owned()));
+
+        assert_eq!(layout.status(&TagTree::default()), None);
+    }
+
+    #[test]
+    fn monocle_cycles_clients() {
+        let layout = Monocle;
+        let (mut tree, [a, b, c]) = layout_tree(&layout);
+        let screen = screen();
+
+        assert_eq!(layout.container_by_direction(&tree, &screen, c, Direction::Right), Some(a));
+        assert_eq!(layout.container_by_direction(&tree, &screen, a, Direction::Up), Some(c));
+
+        tree.focus(a);
+        let d = leaf(&mut tree, 4);
+        layout.insert_container(&mut tree, d);
+        assert_eq!(tree.get_split(tree.root()).unwrap().children(), &[a, d, b, c]);
+    }
+}
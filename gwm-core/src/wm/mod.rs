@@ -42,4 +42,8 @@ pub mod msg;
 pub mod config;
 pub mod core;
 pub mod err;
+pub mod state;
 pub mod tree;
+
+#[cfg(test)]
+pub mod testing;
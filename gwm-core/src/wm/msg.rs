@@ -32,9 +32,12 @@
  * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
  */
 
+use std::collections::HashSet;
 use std::str::FromStr;
 
+use wm::config::{Tag, TagRegistry};
 use wm::layout::{Layout, LayoutContainer};
+use wm::tree::{Arena, ClientId, Direction, ScreenId, SplitType, TagSetId};
 
 /// A message passed to a layout.
 ///
@@ -43,7 +46,10 @@ use wm::layout::{Layout, LayoutContainer};
 declare_hierarchy_with_parser!(Message; match_message,
                                (GenericMessage; "generic"),
                                (MasterFactorMessage; "masterf"),
-                               (MasterNumberMessage; "nmaster"));
+                               (MasterNumberMessage; "nmaster"),
+                               (GridMessage; "grid"),
+                               (RatioMessage; "ratio"),
+                               (GapMessage; "gaps"));
 
 /// A generic message that is interpreted by any layout, by dispatch performed outside of the
 /// layout implementation.
@@ -51,15 +57,248 @@ declare_hierarchy_with_parser!(Message; match_message,
 pub enum GenericMessage {
     // Add a new client on the tag tree managed by the layout.
     // AddClient(ClientId),
+    /// Focus the next tab in the tabbed container closest to the focused container.
+    TabNext,
+    /// Focus the previous tab in the tabbed container closest to the focused container.
+    TabPrev,
+    /// Focus the container in the given direction.
+    Focus(Direction),
+    /// Swap the focused container with the container in the given direction.
+    Swap(Direction),
+    /// Swap the focused container with the first container, or the second one if it is the
+    /// first already.
+    Zoom,
+    /// Split the focused container, so that new containers share a split of the given type.
+    Split(SplitType),
+    /// Toggle the split type of the focused container's parent between horizontal and vertical.
+    ToggleSplit,
+    /// Grow the focused container in the given direction by the given weight.
+    Grow(Direction, u16),
+    /// Shrink the focused container in the given direction by the given weight.
+    Shrink(Direction, u16),
+    /// Reset the weights of the focused container's siblings and their subtrees.
+    Equalize,
+    /// Reset the weights of the whole tree.
+    EqualizeAll,
+    /// Create a new tagset with the given tags and the default layouts.
+    CreateTagSet(Vec<String>),
+    /// Add tags to a tagset.
+    AddTags(TagSetId, Vec<String>),
+    /// Remove tags from a tagset.
+    RemoveTags(TagSetId, Vec<String>),
+    /// Change the layout of a tagset.
+    SetLayout(TagSetId, LayoutContainer),
+    /// Switch to the next layout of the focused tagset.
+    NextLayout,
+    /// Switch to the previous layout of the focused tagset.
+    PrevLayout,
+    /// Switch to the layout with the given name among the focused tagset's layouts.
+    SelectLayout(String),
+    /// Destroy a tagset that isn't shown on any screen.
+    DestroyTagSet(TagSetId),
+    /// Select the focused container, or clear the selection.
+    ToggleSelection,
+    /// Edit the tags of the clients in the focused or selected container.
+    EditClientTags(TagOperation, ClientSelector, Vec<String>),
+    /// Show a tagset on the focused screen.
+    ShowTagSet(TagSetId),
+    /// Go back to the tagset shown previously on the focused screen.
+    HistoryBack,
+    /// Go forward to the tagset left by going back on the focused screen.
+    HistoryForward,
+    /// Toggle between the current and the previously shown tagset on the focused screen.
+    HistoryToggle,
+    /// Show the focused screen's tagset combined with the given tags.
+    ViewCombined(SetOperation, Vec<String>),
+    /// Focus the screen in the given direction.
+    FocusScreen(Direction),
+    /// Swap the tagsets shown on two screens.
+    SwapScreens(ScreenId, ScreenId),
+    /// Move the focused screen's tagset to the screen in the given direction.
+    MoveTagSet(Direction),
+    /// Show or hide the scratchpad on the focused screen.
+    ToggleScratchpad,
+    /// Attach a mark to the focused container.
+    SetMark(String),
+    /// Remove a mark from the container carrying it.
+    UnsetMark(String),
+    /// Focus the container carrying a mark.
+    FocusMark(String),
+    /// Swap the focused container with the container carrying a mark.
+    SwapWithMark(String),
+    /// Move the focused container next to the container carrying a mark.
+    MoveToMark(String),
+    /// Hide the focused client.
+    Iconify,
+    /// Restore the given hidden client, or the client hidden last.
+    Restore(Option<ClientId>),
+    /// Save the state of the arena, to be restored on the next start.
+    SaveState,
+    /// Save the state of the arena and exit the window manager.
+    Quit,
+    /// Save the state of the arena and replace the window manager with a new instance.
+    Restart,
 }
 
 impl GenericMessage {
-    fn parse_from_words(_: &[&str]) -> Option<Self> {
-        // TODO: implement
+    fn parse_from_words(words: &[&str]) -> Option<Self> {
+        match words {
+            ["tab", "next"] => Some(GenericMessage::TabNext),
+            ["tab", "prev"] => Some(GenericMessage::TabPrev),
+            ["focus", dir] => Direction::from_word(dir).map(GenericMessage::Focus),
+            ["swap", dir] => Direction::from_word(dir).map(GenericMessage::Swap),
+            ["zoom"] => Some(GenericMessage::Zoom),
+            ["split", split_type] => SplitType::from_word(split_type).map(GenericMessage::Split),
+            ["grow", dir, amount] => parse_resize(dir, amount, GenericMessage::Grow),
+            ["shrink", dir, amount] => parse_resize(dir, amount, GenericMessage::Shrink),
+            ["equalize"] => Some(GenericMessage::Equalize),
+            ["equalize", "all"] => Some(GenericMessage::EqualizeAll),
+            ["tagset", "create", ref tags @ ..] => {
+                parse_tags(tags).map(GenericMessage::CreateTagSet)
+            },
+            ["tagset", "add", id, ref tags @ ..] => {
+                let id = TagSetId::from_word(id)?;
+                parse_tags(tags).map(|tags| GenericMessage::AddTags(id, tags))
+            },
+            ["tagset", "remove", id, ref tags @ ..] => {
+                let id = TagSetId::from_word(id)?;
+                parse_tags(tags).map(|tags| GenericMessage::RemoveTags(id, tags))
+            },
+            ["tagset", "layout", id, ref layout @ ..] => {
+                let id = TagSetId::from_word(id)?;
+                LayoutContainer::parse_from_words(layout)
+                    .map(|layout| GenericMessage::SetLayout(id, layout))
+            },
+            ["layout", "next"] => Some(GenericMessage::NextLayout),
+            ["layout", "prev"] => Some(GenericMessage::PrevLayout),
+            ["layout", "toggle-split"] => Some(GenericMessage::ToggleSplit),
+            ["layout", name] => Some(GenericMessage::SelectLayout((*name).to_owned())),
+            ["tagset", "destroy", id] => TagSetId::from_word(id).map(GenericMessage::DestroyTagSet),
+            ["select"] => Some(GenericMessage::ToggleSelection),
+            ["tags", op, "selected", ref tags @ ..] => {
+                parse_tag_edit(op, ClientSelector::Selected, tags)
+            },
+            ["tags", op, ref tags @ ..] => parse_tag_edit(op, ClientSelector::Focused, tags),
+            ["view", "back"] => Some(GenericMessage::HistoryBack),
+            ["view", "forward"] => Some(GenericMessage::HistoryForward),
+            ["view", "toggle"] => Some(GenericMessage::HistoryToggle),
+            ["view", "union", ref tags @ ..] => {
+                parse_tags(tags).map(|tags| GenericMessage::ViewCombined(SetOperation::Union, tags))
+            },
+            ["view", "difference", ref tags @ ..] => {
+                parse_tags(tags)
+                    .map(|tags| GenericMessage::ViewCombined(SetOperation::Difference, tags))
+            },
+            ["view", "intersection", ref tags @ ..] => {
+                parse_tags(tags)
+                    .map(|tags| GenericMessage::ViewCombined(SetOperation::Intersection, tags))
+            },
+            ["view", id] => TagSetId::from_word(id).map(GenericMessage::ShowTagSet),
+            ["screen", "focus", dir] => Direction::from_word(dir).map(GenericMessage::FocusScreen),
+            ["screen", "swap", a, b] => {
+                let a = ScreenId::from_word(a)?;
+                ScreenId::from_word(b).map(|b| GenericMessage::SwapScreens(a, b))
+            },
+            ["screen", "move", dir] => Direction::from_word(dir).map(GenericMessage::MoveTagSet),
+            ["scratchpad"] => Some(GenericMessage::ToggleScratchpad),
+            ["mark", "set", mark] => Some(GenericMessage::SetMark((*mark).to_owned())),
+            ["mark", "unset", mark] => Some(GenericMessage::UnsetMark((*mark).to_owned())),
+            ["mark", "focus", mark] => Some(GenericMessage::FocusMark((*mark).to_owned())),
+            ["mark", "swap", mark] => Some(GenericMessage::SwapWithMark((*mark).to_owned())),
+            ["mark", "move", mark] => Some(GenericMessage::MoveToMark((*mark).to_owned())),
+            ["iconify"] => Some(GenericMessage::Iconify),
+            ["restore"] => Some(GenericMessage::Restore(None)),
+            ["restore", id] => ClientId::from_word(id).map(|id| GenericMessage::Restore(Some(id))),
+            ["state", "save"] => Some(GenericMessage::SaveState),
+            ["quit"] => Some(GenericMessage::Quit),
+            ["restart"] => Some(GenericMessage::Restart),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a non-empty list of tag names.
+///
+/// Tags are referred to by name in messages. The names are resolved when the message is
+/// interpreted, creating tags that don't exist yet. Reserved names are rejected.
+fn parse_tags(words: &[&str]) -> Option<Vec<String>> {
+    if words.is_empty() || words.iter().any(|w| TagRegistry::is_reserved(w)) {
         None
+    } else {
+        Some(words.iter().map(|w| (*w).to_owned()).collect())
+    }
+}
+
+/// An operation editing the tags of a client.
+#[derive(Debug)]
+pub enum TagOperation {
+    /// Replace the client's tags.
+    Set,
+    /// Add the tags to the client's tags.
+    Add,
+    /// Remove the tags from the client's tags.
+    Remove,
+    /// Add each tag the client doesn't have, and remove each tag it has.
+    Toggle,
+}
+
+impl TagOperation {
+    /// Parse a tag operation from a single word.
+    fn from_word(word: &str) -> Option<TagOperation> {
+        match word {
+            "set" => Some(TagOperation::Set),
+            "add" => Some(TagOperation::Add),
+            "remove" => Some(TagOperation::Remove),
+            "toggle" => Some(TagOperation::Toggle),
+            _ => None,
+        }
+    }
+
+    /// Compute the tags of a client after applying the operation.
+    fn apply(&self, old: &HashSet<Tag>, tags: &HashSet<Tag>) -> HashSet<Tag> {
+        match *self {
+            TagOperation::Set => tags.clone(),
+            TagOperation::Add => old.union(tags).cloned().collect(),
+            TagOperation::Remove => old.difference(tags).cloned().collect(),
+            TagOperation::Toggle => old.symmetric_difference(tags).cloned().collect(),
+        }
     }
 }
 
+/// An operation combining the tags shown on a screen with other tags.
+#[derive(Debug)]
+pub enum SetOperation {
+    /// Show clients carrying any of the tags as well.
+    Union,
+    /// Don't show clients carrying the tags, unless they carry another tag shown.
+    Difference,
+    /// Show only clients also carrying all of the tags.
+    Intersection,
+}
+
+/// The container whose clients a message refers to.
+#[derive(Debug)]
+pub enum ClientSelector {
+    /// The focused container.
+    Focused,
+    /// The selected container.
+    Selected,
+}
+
+/// Parse a message editing client tags from the operation, selector and tags given.
+fn parse_tag_edit(op: &str, selector: ClientSelector, tags: &[&str]) -> Option<GenericMessage> {
+    let op = TagOperation::from_word(op)?;
+    parse_tags(tags).map(|tags| GenericMessage::EditClientTags(op, selector, tags))
+}
+
+/// Parse the direction and amount of a resizing message.
+fn parse_resize<F>(dir: &str, amount: &str, f: F) -> Option<GenericMessage>
+    where F: FnOnce(Direction, u16) -> GenericMessage
+{
+    let dir = Direction::from_word(dir)?;
+    u16::from_str(amount).ok().map(|amount| f(dir, amount))
+}
+
 /// A message manipulating the master factor of a layout.
 ///
 /// A master factor, if supported by a layout, is a percentage which the layout uses to assign
@@ -115,6 +354,117 @@ impl MasterNumberMessage {
     }
 }
 
+/// A message changing the preferred orientation of a grid layout.
+#[derive(Debug)]
+pub enum GridMessage {
+    /// Prefer more columns than rows.
+    Columns,
+    /// Prefer more rows than columns.
+    Rows,
+    /// Switch between the two preferences.
+    Toggle,
+}
+
+impl GridMessage {
+    fn parse_from_words(words: &[&str]) -> Option<Self> {
+        match words {
+            ["columns"] => Some(GridMessage::Columns),
+            ["rows"] => Some(GridMessage::Rows),
+            ["toggle"] => Some(GridMessage::Toggle),
+            _ => None,
+        }
+    }
+}
+
+/// A message manipulating the split ratio of a layout.
+///
+/// A split ratio, if supported by a layout, is the percentage of the remaining space each
+/// client takes, leaving the rest to the following clients.
+#[derive(Debug)]
+pub enum RatioMessage {
+    /// Set the absolute value of the split ratio, saturated to 100.
+    Absolute(u8),
+    /// Increase the value of the split ratio by the given amount, capped to 100.
+    Increase(u8),
+    /// Decrease the value of the split ratio by the given amount, saturated to 0.
+    Decrease(u8),
+}
+
+impl RatioMessage {
+    fn parse_from_words(words: &[&str]) -> Option<Self> {
+        if words.len() < 2 {
+            return None;
+        }
+
+        match words[0] {
+            "abs" => u8::from_str(words[1]).ok().map(RatioMessage::Absolute),
+            "inc" => u8::from_str(words[1]).ok().map(RatioMessage::Increase),
+            "dec" => u8::from_str(words[1]).ok().map(RatioMessage::Decrease),
+            _ => None,
+        }
+    }
+}
+
+/// A message manipulating the gaps of the tagset shown on the focused screen.
+///
+/// Gaps are applied independently of the layout used, so these messages are interpreted by the
+/// arena itself.
+#[derive(Debug)]
+pub enum GapMessage {
+    /// Change the gap between neighbouring clients.
+    Inner(GapChange),
+    /// Change the gap between clients and the edges of the screen.
+    Outer(GapChange),
+    /// Enable or disable smart gaps, or toggle them if no value is given.
+    Smart(Option<bool>),
+}
+
+/// A change of a gap's size.
+#[derive(Debug)]
+pub enum GapChange {
+    /// Set the gap to the given number of pixels.
+    Absolute(u32),
+    /// Increase the gap by the given number of pixels.
+    Increase(u32),
+    /// Decrease the gap by the given number of pixels, saturated to 0.
+    Decrease(u32),
+}
+
+impl GapMessage {
+    fn parse_from_words(words: &[&str]) -> Option<Self> {
+        match words {
+            ["inner", op, amount] => GapChange::parse(op, amount).map(GapMessage::Inner),
+            ["outer", op, amount] => GapChange::parse(op, amount).map(GapMessage::Outer),
+            ["smart", "on"] => Some(GapMessage::Smart(Some(true))),
+            ["smart", "off"] => Some(GapMessage::Smart(Some(false))),
+            ["smart", "toggle"] => Some(GapMessage::Smart(None)),
+            _ => None,
+        }
+    }
+}
+
+impl GapChange {
+    fn parse(op: &str, amount: &str) -> Option<Self> {
+        let amount = u32::from_str(amount).ok()?;
+
+        match op {
+            "abs" => Some(GapChange::Absolute(amount)),
+            "inc" => Some(GapChange::Increase(amount)),
+            "dec" => Some(GapChange::Decrease(amount)),
+            _ => None,
+        }
+    }
+
+    /// Apply the change to a gap.
+    fn apply(&self, gap: u32) -> u32 {
+        match *self {
+            GapChange::Absolute(amount) => amount,
+            GapChange::Increase(amount) => gap.saturating_add(amount),
+            GapChange::Decrease(amount) => gap.saturating_sub(amount),
+        }
+    }
+}
+
 impl LayoutContainer {
     /// Pass a message to the layout and signify whether it was accepted.
     pub fn accept_msg(&mut self, msg: Message) -> bool {
@@ -123,3 +473,175 @@ impl LayoutContainer {
         )
     }
 }
+
+impl Arena {
+    /// Pass a message to the arena and signify whether it was accepted.
+    ///
+    /// Generic and gap messages are interpreted by the arena itself, all other messages are
+    /// passed to the layout of the tagset shown on the focused screen.
+    pub fn accept_msg(&mut self, msg: Message) -> bool {
+        match msg {
+            Message::GenericMessage(msg) => self.accept_generic_msg(msg),
+            Message::GapMessage(msg) => self.accept_gap_msg(msg),
+            msg => self.focused_tagset_mut().layout_mut().accept_msg(msg),
+        }
+    }
+
+    /// Change the gaps of the tagset shown on the focused screen.
+    fn accept_gap_msg(&mut self, msg: GapMessage) -> bool {
+        let gaps = self.focused_tagset_mut().gaps_mut();
+
+        match msg {
+            GapMessage::Inner(change) => gaps.inner = change.apply(gaps.inner),
+            GapMessage::Outer(change) => gaps.outer = change.apply(gaps.outer),
+            GapMessage::Smart(smart) => gaps.smart = smart.unwrap_or(!gaps.smart),
+        }
+
+        true
+    }
+
+    /// Interpret a generic message and signify whether it was accepted.
+    fn accept_generic_msg(&mut self, msg: GenericMessage) -> bool {
+        match msg {
+            GenericMessage::TabNext => self.cycle_tab(true),
+            GenericMessage::TabPrev => self.cycle_tab(false),
+            GenericMessage::Focus(dir) => self.focus_direction(dir),
+            GenericMessage::Swap(dir) => self.swap_direction(dir),
+            GenericMessage::Zoom => self.zoom(),
+            GenericMessage::Split(split_type) => self.split(split_type),
+            GenericMessage::ToggleSplit => self.toggle_split(),
+            GenericMessage::Grow(dir, amount) => self.resize(dir, i32::from(amount)),
+            GenericMessage::Shrink(dir, amount) => self.resize(dir, -i32::from(amount)),
+            GenericMessage::Equalize => self.equalize(false),
+            GenericMessage::EqualizeAll => self.equalize(true),
+            GenericMessage::CreateTagSet(tags) => {
+                let tags = self.resolve_tags(&tags);
+                let id = self.create_tagset(tags);
+                info!("created tagset {:?}", id);
+                true
+            },
+            GenericMessage::AddTags(id, tags) => {
+                let tags = self.resolve_tags(&tags);
+                self.add_tags(id, tags)
+            },
+            GenericMessage::RemoveTags(id, tags) => {
+                let tags = self.resolve_tags(&tags);
+                self.remove_tags(id, &tags)
+            },
+            GenericMessage::SetLayout(id, layout) => self.set_layout(id, layout),
+            GenericMessage::NextLayout => self.cycle_layout(true),
+            GenericMessage::PrevLayout => self.cycle_layout(false),
+            GenericMessage::SelectLayout(name) => self.select_layout(&name),
+            GenericMessage::DestroyTagSet(id) => self.destroy_tagset(id),
+            GenericMessage::ToggleSelection => self.toggle_selection(),
+            GenericMessage::EditClientTags(op, selector, tags) => {
+                let tags = self.resolve_tags(&tags);
+                self.edit_client_tags(&op, &selector, &tags)
+            },
+            GenericMessage::ShowTagSet(id) => self.show_tagset(id),
+            GenericMessage::HistoryBack => self.history_back(),
+            GenericMessage::HistoryForward => self.history_forward(),
+            GenericMessage::HistoryToggle => self.history_toggle(),
+            GenericMessage::ViewCombined(op, tags) => {
+                let tags = self.resolve_tags(&tags);
+                match op {
+                    SetOperation::Union => self.view_union(&tags),
+                    SetOperation::Difference => self.view_difference(&tags),
+                    SetOperation::Intersection => self.view_intersection(&tags),
+                }
+            },
+            GenericMessage::FocusScreen(dir) => {
+                let focused = self.focused_screen();
+                match self.screen_by_direction(focused, dir) {
+                    Some(screen) => self.focus_screen(screen),
+                    None => false,
+                }
+            },
+            GenericMessage::SwapScreens(a, b) => self.swap_screens(a, b),
+            GenericMessage::MoveTagSet(dir) => self.move_tagset(dir),
+            GenericMessage::SetMark(mark) => self.set_mark(mark),
+            GenericMessage::UnsetMark(mark) => self.unset_mark(&mark),
+            GenericMessage::FocusMark(mark) => self.focus_mark(&mark),
+            GenericMessage::SwapWithMark(mark) => self.swap_with_mark(&mark),
+            GenericMessage::MoveToMark(mark) => self.move_to_mark(&mark),
+            GenericMessage::Iconify => self.iconify_focused(),
+            GenericMessage::Restore(id) => self.restore(id),
+            // these are handled by the core, as they involve more than the arena
+            GenericMessage::ToggleScratchpad |
+            GenericMessage::SaveState |
+            GenericMessage::Quit |
+            GenericMessage::Restart => false,
+        }
+    }
+
+    /// Edit the tags of all clients in the focused or selected container on the focused screen.
+    ///
+    /// Edits that would leave a client without any tags are refused, as the client would not be
+    /// shown on any tagset anymore.
+    fn edit_client_tags(&mut self,
+                        op: &TagOperation,
+                        selector: &ClientSelector,
+                        tags: &HashSet<Tag>) -> bool {
+        let clients = {
+            let tree = self.focused_tagset().tree();
+            let target = match *selector {
+                ClientSelector::Focused => tree.focused(),
+                ClientSelector::Selected => tree.selected(),
+            };
+
+            match target {
+                Some(target) => tree.subtree_clients(target),
+                None => return false,
+            }
+        };
+
+        let mut accepted = false;
+
+        for id in clients {
+            let new_tags = match self.client(id) {
+                Some(client) => op.apply(client.tags(), tags),
+                None => continue,
+            };
+
+            if !new_tags.is_empty() {
+                self.set_client_tags(id, new_tags);
+                accepted = true;
+            }
+        }
+
+        accepted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wm::testing::*;
+
+    fn parse(msg: &str) -> String {
+        let words: Vec<&str> = msg.split_whitespace().collect();
+        format!("{:?}", Message::parse_from_words(&words))
+    }
+
+    #[test]
+    fn parse_core_messages() {
+        assert_eq!(parse("generic restart"), "Some(GenericMessage(Restart))");
+        assert_eq!(parse("generic quit"), "Some(GenericMessage(Quit))");
+        assert_eq!(parse("generic state save"), "Some(GenericMessage(SaveState))");
+        assert_eq!(parse("generic restart now"), "None");
+    }
+
+    #[test]
+    fn core_messages_leave_the_arena_alone() {
+        let mut arena = arena(&["manual"]);
+        add_clients(&mut arena, &[1, 2]);
+        let before = dump(&arena);
+
+        for msg in &["restart", "quit", "state save"] {
+            assert!(!accept(&mut arena, &format!("generic {}", msg)));
+        }
+
+        assert_eq!(dump(&arena), before);
+    }
+}
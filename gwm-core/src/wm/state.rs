@@ -0,0 +1,360 @@
+/*
+ * Copyright Inokentiy Babushkin and contributors (c) 2016-2017
+ *
+ * All rights reserved.
+
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions
+ * are met:
+ *
+ *     * Redistributions of source code must retain the above copyright
+ *       notice, this list of conditions and the following disclaimer.
+ *
+ *     * Redistributions in binary form must reproduce the above
+ *       copyright notice, this list of conditions and the following
+ *       disclaimer in the documentation and/or other materials provided
+ *       with the distribution.
+ *
+ *     * Neither the name of Inokentiy Babushkin nor the names of other
+ *       contributors may be used to endorse or promote products derived
+ *       from this software without specific prior written permission.
+
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+ * "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+ * LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+ * A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+ * OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+ * LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+ * DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+ * THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use toml::value::{Table, Value};
+
+use wm::config::{as_bool, as_int, as_string, as_table, extract_array, extract_strings,
+                 extract_value, opt_key};
+use wm::err::{WmError, WmResult};
+use wm::tree::{ClientId, Gaps, Geometry, SplitType};
+
+/// A snapshot of the arena, as saved across restarts.
+///
+/// Tags are referred to by name, and layouts by their word-based representation, so that a
+/// snapshot can be restored with a different configuration.
+pub struct ArenaState {
+    /// The clients managed.
+    pub clients: Vec<ClientState>,
+    /// The hidden clients, in the order they have been hidden.
+    pub iconified: Vec<ClientId>,
+    /// The tagsets present.
+    pub tagsets: Vec<TagSetState>,
+    /// The tagsets shown on the screens, in order.
+    pub screens: Vec<u16>,
+    /// The screen focused.
+    pub focused_screen: u8,
+}
+
+/// A snapshot of a client.
+pub struct ClientState {
+    /// The client's window.
+    pub window: ClientId,
+    /// The names of the tags attached to the client.
+    pub tags: Vec<String>,
+    /// The client's last-configured geometry.
+    pub geometry: Geometry,
+}
+
+/// A snapshot of a tagset.
+pub struct TagSetState {
+    /// The tagset's id.
+    pub id: u16,
+    /// The names of the tags wrapped by the tagset.
+    pub tags: Vec<String>,
+    /// The names of the tags every client visible on the tagset has to carry.
+    pub required: Vec<String>,
+    /// The word-based representations of the tagset's layouts, joined by spaces.
+    pub layouts: Vec<String>,
+    /// The index of the layout used.
+    pub layout: usize,
+    /// The tagset's gaps.
+    pub gaps: Gaps,
+    /// The shape of the tagset's tree.
+    pub tree: ContainerState,
+    /// The shape of the tagset's tree before it had to be altered for the layout used, if any.
+    pub shadow: Option<ContainerState>,
+    /// Whether the tagset is destroyed once it is left.
+    pub transient: bool,
+}
+
+/// A snapshot of a container and it's descendants.
+#[derive(Clone, PartialEq)]
+pub struct ContainerState {
+    /// The kind of the container.
+    pub kind: ContainerKind,
+    /// The weight of the container in it's parent.
+    pub weight: u32,
+    /// Whether the container is marked floating.
+    pub floating: bool,
+    /// Whether the container is focused.
+    pub focused: bool,
+    /// The marks carried by the container.
+    pub marks: Vec<String>,
+}
+
+/// The kind of a container in a snapshot.
+#[derive(Clone, PartialEq)]
+pub enum ContainerKind {
+    /// A split container with the given split type, last-focused child, and children.
+    Split(SplitType, Option<usize>, Vec<ContainerState>),
+    /// A client container.
+    Client(ClientId),
+}
+
+impl ArenaState {
+    /// Write the snapshot to a file.
+    pub fn save(&self, path: &Path) -> WmResult<()> {
+        let mut table = Table::new();
+
+        table.insert("clients".to_owned(),
+                     Value::Array(self.clients.iter().map(ClientState::to_value).collect()));
+        table.insert("iconified".to_owned(),
+                     Value::Array(self.iconified.iter().map(|c| int(c.0)).collect()));
+        table.insert("tagsets".to_owned(),
+                     Value::Array(self.tagsets.iter().map(TagSetState::to_value).collect()));
+        table.insert("screens".to_owned(),
+                     Value::Array(self.screens.iter().map(|&s| int(s)).collect()));
+        table.insert("focused_screen".to_owned(), int(self.focused_screen));
+
+        File::create(path)
+            .and_then(|mut file| file.write_all(Value::Table(table).to_string().as_bytes()))
+            .map_err(WmError::StateIOError)
+    }
+
+    /// Read a snapshot from a file.
+    pub fn load(path: &Path) -> WmResult<ArenaState> {
+        let mut toml_str = String::new();
+
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut toml_str))
+            .map_err(WmError::StateIOError)?;
+
+        let mut table = match toml_str.parse::<Value>().map_err(WmError::TomlError)? {
+            Value::Table(t) => t,
+            _ => return Err(WmError::TomlNotTable),
+        };
+
+        let clients = extract_array(&mut table, "clients")?
+            .into_iter()
+            .map(ClientState::from_value)
+            .collect::<WmResult<_>>()?;
+        let iconified = extract_array(&mut table, "iconified")?
+            .into_iter()
+            .map(|v| as_int(v, "iconified").map(ClientId))
+            .collect::<WmResult<_>>()?;
+        let tagsets = extract_array(&mut table, "tagsets")?
+            .into_iter()
+            .map(TagSetState::from_value)
+            .collect::<WmResult<_>>()?;
+        let screens = extract_array(&mut table, "screens")?
+            .into_iter()
+            .map(|v| as_int(v, "screens"))
+            .collect::<WmResult<_>>()?;
+        let focused_screen =
+            as_int(extract_value(&mut table, "focused_screen")?, "focused_screen")?;
+
+        Ok(ArenaState {
+            clients,
+            iconified,
+            tagsets,
+            screens,
+            focused_screen,
+        })
+    }
+}
+
+impl ClientState {
+    fn to_value(&self) -> Value {
+        let mut table = Table::new();
+        let geometry = &self.geometry;
+
+        table.insert("window".to_owned(), int(self.window.0));
+        table.insert("tags".to_owned(), strings(&self.tags));
+        table.insert("geometry".to_owned(),
+                     Value::Array(vec![int(geometry.x),
+                                       int(geometry.y),
+                                       int(geometry.width),
+                                       int(geometry.height)]));
+
+        Value::Table(table)
+    }
+
+    fn from_value(value: Value) -> WmResult<ClientState> {
+        let mut table = as_table(value, "clients")?;
+        let geometry = extract_array(&mut table, "geometry")?
+            .into_iter()
+            .map(|v| as_int(v, "geometry"))
+            .collect::<WmResult<Vec<u32>>>()?;
+
+        if geometry.len() != 4 {
+            return Err(WmError::KeyTypeMismatch("geometry".to_owned()));
+        }
+
+        Ok(ClientState {
+            window: ClientId(as_int(extract_value(&mut table, "window")?, "window")?),
+            tags: extract_strings(&mut table, "tags")?,
+            geometry: Geometry {
+                x: geometry[0],
+                y: geometry[1],
+                width: geometry[2],
+                height: geometry[3],
+            },
+        })
+    }
+}
+
+impl TagSetState {
+    fn to_value(&self) -> Value {
+        let mut table = Table::new();
+
+        table.insert("id".to_owned(), int(self.id));
+        table.insert("tags".to_owned(), strings(&self.tags));
+        table.insert("required".to_owned(), strings(&self.required));
+        table.insert("layouts".to_owned(), strings(&self.layouts));
+        table.insert("layout".to_owned(), int(self.layout as i64));
+        table.insert("inner_gaps".to_owned(), int(self.gaps.inner));
+        table.insert("outer_gaps".to_owned(), int(self.gaps.outer));
+        table.insert("smart_gaps".to_owned(), Value::Boolean(self.gaps.smart));
+        table.insert("tree".to_owned(), self.tree.to_value());
+        table.insert("transient".to_owned(), Value::Boolean(self.transient));
+
+        if let Some(ref shadow) = self.shadow {
+            table.insert("shadow".to_owned(), shadow.to_value());
+        }
+
+        Value::Table(table)
+    }
+
+    fn from_value(value: Value) -> WmResult<TagSetState> {
+        let mut table = as_table(value, "tagsets")?;
+
+        Ok(TagSetState {
+            id: as_int(extract_value(&mut table, "id")?, "id")?,
+            tags: extract_strings(&mut table, "tags")?,
+            required: extract_strings(&mut table, "required")?,
+            layouts: extract_strings(&mut table, "layouts")?,
+            layout: as_int(extract_value(&mut table, "layout")?, "layout")?,
+            gaps: Gaps {
+                inner: as_int(extract_value(&mut table, "inner_gaps")?, "inner_gaps")?,
+                outer: as_int(extract_value(&mut table, "outer_gaps")?, "outer_gaps")?,
+                smart: as_bool(extract_value(&mut table, "smart_gaps")?, "smart_gaps")?,
+            },
+            tree: ContainerState::from_value(extract_value(&mut table, "tree")?)?,
+            shadow: match opt_key(extract_value(&mut table, "shadow"))? {
+                Some(shadow) => Some(ContainerState::from_value(shadow)?),
+                None => None,
+            },
+            transient: match opt_key(extract_value(&mut table, "transient"))? {
+                Some(transient) => as_bool(transient, "transient")?,
+                None => false,
+            },
+        })
+    }
+}
+
+impl ContainerState {
+    /// Remove a mark from the snapshot and signify whether it was present.
+    pub fn unset_mark(&mut self, mark: &str) -> bool {
+        let before = self.marks.len();
+        self.marks.retain(|m| m != mark);
+        let mut found = self.marks.len() != before;
+
+        if let ContainerKind::Split(_, _, ref mut children) = self.kind {
+            for child in children {
+                found |= child.unset_mark(mark);
+            }
+        }
+
+        found
+    }
+
+    fn to_value(&self) -> Value {
+        let mut table = Table::new();
+
+        match self.kind {
+            ContainerKind::Split(split_type, last_focused, ref children) => {
+                let split_type = match split_type {
+                    SplitType::Horizontal => "horizontal",
+                    SplitType::Vertical => "vertical",
+                    SplitType::Tabbed => "tabbed",
+                };
+
+                table.insert("split".to_owned(), Value::String(split_type.to_owned()));
+                if let Some(last_focused) = last_focused {
+                    table.insert("last_focused".to_owned(), int(last_focused as u32));
+                }
+                table.insert("children".to_owned(),
+                             Value::Array(children.iter().map(ContainerState::to_value).collect()));
+            },
+            ContainerKind::Client(client) => {
+                table.insert("client".to_owned(), int(client.0));
+            },
+        }
+
+        table.insert("weight".to_owned(), int(self.weight));
+        table.insert("floating".to_owned(), Value::Boolean(self.floating));
+        table.insert("focused".to_owned(), Value::Boolean(self.focused));
+        table.insert("marks".to_owned(), strings(&self.marks));
+
+        Value::Table(table)
+    }
+
+    fn from_value(value: Value) -> WmResult<ContainerState> {
+        let mut table = as_table(value, "tree")?;
+
+        let kind = if let Some(client) = table.remove("client") {
+            ContainerKind::Client(ClientId(as_int(client, "client")?))
+        } else {
+            let split_type = as_string(extract_value(&mut table, "split")?, "split")?;
+            let split_type = match split_type.as_str() {
+                "horizontal" => SplitType::Horizontal,
+                "vertical" => SplitType::Vertical,
+                "tabbed" => SplitType::Tabbed,
+                _ => return Err(WmError::KeyTypeMismatch("split".to_owned())),
+            };
+            let last_focused = match table.remove("last_focused") {
+                Some(v) => Some(as_int::<u32>(v, "last_focused")? as usize),
+                None => None,
+            };
+            let children = extract_array(&mut table, "children")?
+                .into_iter()
+                .map(ContainerState::from_value)
+                .collect::<WmResult<_>>()?;
+
+            ContainerKind::Split(split_type, last_focused, children)
+        };
+
+        Ok(ContainerState {
+            kind,
+            weight: as_int(extract_value(&mut table, "weight")?, "weight")?,
+            floating: as_bool(extract_value(&mut table, "floating")?, "floating")?,
+            focused: as_bool(extract_value(&mut table, "focused")?, "focused")?,
+            marks: extract_strings(&mut table, "marks")?,
+        })
+    }
+}
+
+/// Construct a TOML integer.
+fn int<T: Into<i64>>(i: T) -> Value {
+    Value::Integer(i.into())
+}
+
+/// Construct a TOML array of strings.
+fn strings(strings: &[String]) -> Value {
+    Value::Array(strings.iter().cloned().map(Value::String).collect())
+}
@@ -0,0 +1,158 @@
+/*
+ * Copyright Inokentiy Babushkin and contributors (c) 2016-2017
+ *
+ * All rights reserved.
+
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions
+ * are met:
+ *
+ *     * Redistributions of source code must retain the above copyright
+ *       notice, this list of conditions and the following disclaimer.
+ *
+ *     * Redistributions in binary form must reproduce the above
+ *       copyright notice, this list of conditions and the following
+ *       disclaimer in the documentation and/or other materials provided
+ *       with the distribution.
+ *
+ *     * Neither the name of Inokentiy Babushkin nor the names of other
+ *       contributors may be used to endorse or promote products derived
+ *       from this software without specific prior written permission.
+
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+ * "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+ * LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+ * A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+ * OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+ * LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+ * DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+ * THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+use wm::config::{arena_init, Config};
+use wm::layout::{Layout, LayoutContainer};
+use wm::msg::Message;
+use wm::tree::{Arena, Client, ClientContainer, ClientId, ClientProperties, Container,
+               ContainerId, Geometry, Screen, SplitType, TagTree, WindowSizes, DEFAULT_TAGSET};
+
+/// The geometry of the screen used by all fixtures.
+pub const SCREEN: Geometry = Geometry { x: 0, y: 0, width: 120, height: 90 };
+
+/// Get a screen with the fixtures' geometry.
+pub fn screen() -> Screen {
+    Screen::new(SCREEN, DEFAULT_TAGSET)
+}
+
+/// Allocate a detached client container for a window.
+pub fn leaf(tree: &mut TagTree, window: u32) -> ContainerId {
+    tree.alloc(Container::Client(ClientContainer::new(ClientId(window))))
+}
+
+/// Build a tree holding `1 | (2 / 3)` with `3` focused, returning the leaves.
+pub fn nested_tree() -> (TagTree, [ContainerId; 3]) {
+    let mut tree = TagTree::default();
+    let root = tree.root();
+    tree.split(root, SplitType::Horizontal);
+
+    let a = leaf(&mut tree, 1);
+    let b = leaf(&mut tree, 2);
+    let c = leaf(&mut tree, 3);
+    tree.append_child(root, a);
+    tree.append_child(root, b);
+    tree.split(b, SplitType::Vertical);
+    tree.attach_after(c, b);
+    tree.focus(c);
+
+    (tree, [a, b, c])
+}
+
+/// Build a tree by inserting the clients `1`, `2` and `3` through a layout, in that order.
+pub fn layout_tree<L: Layout>(layout: &L) -> (TagTree, [ContainerId; 3]) {
+    let mut tree = TagTree::default();
+    let mut ids = Vec::new();
+
+    for window in 1..4 {
+        let id = leaf(&mut tree, window);
+        layout.insert_container(&mut tree, id);
+        layout.correct_tree(&mut tree);
+        tree.focus(id);
+        ids.push(id);
+    }
+
+    (tree, [ids[0], ids[1], ids[2]])
+}
+
+/// Compute the geometries of a tree on the fixtures' screen.
+pub fn compute<L: Layout>(layout: &L, tree: &TagTree) -> WindowSizes {
+    let mut sizes = WindowSizes::new();
+    layout.compute_geo(tree, &screen(), &mut sizes);
+    sizes
+}
+
+/// Parse a layout from its word-based representation.
+pub fn layout(words: &str) -> LayoutContainer {
+    let words: Vec<&str> = words.split_whitespace().collect();
+    LayoutContainer::parse_from_words(&words).unwrap()
+}
+
+/// Construct an arena with a single screen, cycling through the given layouts.
+pub fn arena(layouts: &[&str]) -> Arena {
+    let layouts = layouts.iter().map(|l| layout(l)).collect();
+    arena_init(Config { layouts, ..Config::default() }, &[SCREEN])
+}
+
+/// Construct a client tagged with the given tags, named after its window.
+pub fn new_client(arena: &mut Arena, window: u32, tags: &[&str]) -> Client {
+    let names: Vec<String> = tags.iter().map(|&t| t.to_owned()).collect();
+    let tags = arena.resolve_tags(&names);
+    let properties = ClientProperties {
+        name: format!("c{}", window),
+        class: Vec::new(),
+    };
+
+    Client::new(ClientId(window), Geometry::default(), properties, tags)
+}
+
+/// Add clients tagged `default` to an arena.
+pub fn add_clients(arena: &mut Arena, windows: &[u32]) {
+    for &window in windows {
+        let client = new_client(arena, window, &["default"]);
+        arena.add_client(client);
+    }
+}
+
+/// Pass a message given in its word-based representation to an arena.
+pub fn accept(arena: &mut Arena, msg: &str) -> bool {
+    let words: Vec<&str> = msg.split_whitespace().collect();
+    arena.accept_msg(Message::parse_from_words(&words).unwrap())
+}
+
+/// Get a dump of the tree shown on the focused screen.
+pub fn dump(arena: &Arena) -> String {
+    let mut out = Vec::new();
+    arena.write_tree(&mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+/// Get an entry of the status stream, without its name.
+pub fn status(arena: &Arena, entry: &str) -> String {
+    let mut out = Vec::new();
+    arena.write_status(&mut out).unwrap();
+
+    String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .find(|l| l.split('\t').next() == Some(entry))
+        .map(|l| l[entry.len()..].trim_start_matches('\t').to_owned())
+        .unwrap()
+}
+
+/// Get the windows of all clients shown, sorted.
+pub fn shown(arena: &mut Arena) -> Vec<u32> {
+    let mut windows: Vec<u32> = arena.arrange().0.keys().map(|c| c.0).collect();
+    windows.sort();
+    windows
+}
@@ -32,29 +32,118 @@
  * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
  */
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::{Result as IoResult, Write};
+use std::iter;
+use std::mem;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 use xcb::xproto;
 
-use wm::config::Tag;
-use wm::layout::LayoutContainer;
+use wm::config::{ScratchpadConfig, Tag, TagRegistry, ViewPolicy, DEFAULT_SCRATCHPAD_SIZE,
+                 SCRATCHPAD};
+use wm::layout::{LayoutContainer, LayoutList};
+use wm::state::{ArenaState, ClientState, ContainerKind, ContainerState, TagSetState};
 
 /// A rectangle somewhere on screen.
 ///
 /// Could represent a client's geometry, a screen, or something else.
-#[derive(Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Geometry {
     /// The x coordinate of the upper left corner of the rectangle.
-    x: u32,
+    pub x: u32,
     /// The y coordinate of the upper left corner of the rectangle.
-    y: u32,
+    pub y: u32,
     /// The width of the rectangle.
-    width: u32,
+    pub width: u32,
     /// The height of the rectangle.
-    height: u32,
+    pub height: u32,
+}
+
+impl Geometry {
+    /// Split the rectangle into columns from left to right, proportionally to the given weights.
+    ///
+    /// Pixels left over by the division are assigned to the last column.
+    pub fn split_horizontal(&self, weights: &[u32]) -> Vec<Geometry> {
+        split_weighted(self.width, weights)
+            .into_iter()
+            .map(|(offset, width)| Geometry {
+                x: self.x + offset,
+                y: self.y,
+                width,
+                height: self.height,
+            })
+            .collect()
+    }
+
+    /// Split the rectangle into rows from top to bottom, proportionally to the given weights.
+    ///
+    /// Pixels left over by the division are assigned to the last row.
+    pub fn split_vertical(&self, weights: &[u32]) -> Vec<Geometry> {
+        split_weighted(self.height, weights)
+            .into_iter()
+            .map(|(offset, height)| Geometry {
+                x: self.x,
+                y: self.y + offset,
+                width: self.width,
+                height,
+            })
+            .collect()
+    }
+
+    /// Get a rectangle of the given size in percent, centred in the rectangle.
+    pub fn centred(&self, width: u32, height: u32) -> Geometry {
+        let width = self.width * width / 100;
+        let height = self.height * height / 100;
+
+        Geometry {
+            x: self.x + (self.width - width) / 2,
+            y: self.y + (self.height - height) / 2,
+            width,
+            height,
+        }
+    }
+}
+
+/// Split a length into parts proportional to the given weights.
+///
+/// Returns the offset and length of each part. If all weights are zero, the length is split
+/// evenly. Every part is at least one pixel long if the length allows it, since X refuses
+/// windows of size zero. Parts too short are lengthened at the expense of their neighbours,
+/// leaving all others as they are.
+fn split_weighted(length: u32, weights: &[u32]) -> Vec<(u32, u32)> {
+    let mut total: u64 = weights.iter().map(|&w| u64::from(w)).sum();
+    let even = total == 0;
+    if even {
+        total = weights.len() as u64;
+    }
+
+    let mut offsets = vec![0];
+    let mut acc = 0;
+
+    for &weight in weights {
+        acc += if even { 1 } else { u64::from(weight) };
+        offsets.push((u64::from(length) * acc / total) as u32);
+    }
+
+    // the last offset is the length itself, so that all parts fit between the bounds
+    let parts = weights.len();
+    if length as usize >= parts {
+        for i in 1..parts {
+            offsets[i] = offsets[i].max(offsets[i - 1] + 1);
+        }
+
+        for i in (1..parts).rev() {
+            offsets[i] = offsets[i].min(offsets[i + 1] - 1);
+        }
+    }
+
+    offsets.windows(2).map(|w| (w[0], w[1] - w[0])).collect()
 }
 
 /// Geometrical direction (in a tagset tree).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Direction {
     /// Geometric, left (towards lower x-coordinates).
     Left,
@@ -78,12 +167,107 @@ pub enum Direction {
     SiblingCycleBackward,
 }
 
+impl Direction {
+    /// Parse a direction from a single word.
+    pub fn from_word(word: &str) -> Option<Direction> {
+        match word {
+            "left" => Some(Direction::Left),
+            "up" => Some(Direction::Up),
+            "right" => Some(Direction::Right),
+            "down" => Some(Direction::Down),
+            "next" => Some(Direction::InOrderForward),
+            "prev" => Some(Direction::InOrderBackward),
+            "pre_next" => Some(Direction::PreOrderForward),
+            "pre_prev" => Some(Direction::PreOrderBackward),
+            "sibling_next" => Some(Direction::SiblingCycleForward),
+            "sibling_prev" => Some(Direction::SiblingCycleBackward),
+            _ => None,
+        }
+    }
+
+    /// Get the split type along which the direction moves, if it is geometric.
+    pub fn split_type(&self) -> Option<SplitType> {
+        match *self {
+            Direction::Left | Direction::Right => Some(SplitType::Horizontal),
+            Direction::Up | Direction::Down => Some(SplitType::Vertical),
+            _ => None,
+        }
+    }
+}
+
 /// A map used to hold client geometries constructed by a layout.
 pub type WindowSizes = HashMap<ClientId, Geometry>;
 
+/// The gaps left between and around clients, independently of the layout used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Gaps {
+    /// The gap between neighbouring clients, in pixels.
+    pub inner: u32,
+    /// The gap between clients and the edges of the screen, in pixels.
+    pub outer: u32,
+    /// Whether to leave out all gaps when only one client is shown.
+    pub smart: bool,
+}
+
+impl Gaps {
+    /// Shrink the geometries computed by a layout for a screen to leave the gaps.
+    ///
+    /// Edges lying on the screen's edges are moved by the outer gap, all other edges by half
+    /// the inner gap. Geometries too small to hold their gaps are left untouched.
+    pub fn apply(&self, screen: &Geometry, sizes: &mut WindowSizes) {
+        if self.smart && sizes.len() < 2 {
+            return;
+        }
+
+        let half = self.inner / 2;
+
+        for geo in sizes.values_mut() {
+            let left = if geo.x <= screen.x { self.outer } else { half };
+            let top = if geo.y <= screen.y { self.outer } else { half };
+            let right = if geo.x + geo.width >= screen.x + screen.width {
+                self.outer
+            } else {
+                self.inner - half
+            };
+            let bottom = if geo.y + geo.height >= screen.y + screen.height {
+                self.outer
+            } else {
+                self.inner - half
+            };
+
+            if left + right < geo.width && top + bottom < geo.height {
+                geo.x += left;
+                geo.y += top;
+                geo.width -= left + right;
+                geo.height -= top + bottom;
+            }
+        }
+    }
+}
+
 /// A unique identifier for clients, in this case provided by the X server.
-#[derive(PartialEq, Eq, Hash)]
-pub struct ClientId(xproto::Window);
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ClientId(pub xproto::Window);
+
+impl ClientId {
+    /// Parse a client id from a single word, given in decimal or in hexadecimal with a `0x`
+    /// prefix.
+    pub fn from_word(word: &str) -> Option<ClientId> {
+        match word.strip_prefix("0x") {
+            Some(hex) => xproto::Window::from_str_radix(hex, 16).ok().map(ClientId),
+            None => xproto::Window::from_str(word).ok().map(ClientId),
+        }
+    }
+}
+
+/// Properties of a client, as set by the client itself.
+#[derive(Debug, Default)]
+pub struct ClientProperties {
+    /// The client's window title.
+    pub name: String,
+    /// The client's `WM_CLASS`, consisting of an instance and a class name.
+    pub class: Vec<String>,
+}
 
 /// A client being managed.
 pub struct Client {
@@ -93,15 +277,76 @@ pub struct Client {
     geometry: Geometry,
     /// Whether the client's window is currently mapped on screen.
     mapped: bool,
-    /// Properties of the client (currently empty).
-    properties: (),
+    /// The number of unmap requests sent by us whose `UnmapNotify` hasn't arrived yet.
+    pending_unmaps: u32,
+    /// Whether the client has been hidden (iconified).
+    ///
+    /// Hidden clients keep their tags, but aren't shown on any tagset.
+    iconic: bool,
+    /// Properties of the client.
+    properties: ClientProperties,
     /// The set of tags attached to the client.
     tags: HashSet<Tag>,
 }
 
+impl Client {
+    pub fn new(window: ClientId,
+               geometry: Geometry,
+               properties: ClientProperties,
+               tags: HashSet<Tag>) -> Client {
+        Client {
+            window,
+            geometry,
+            mapped: false,
+            pending_unmaps: 0,
+            iconic: false,
+            properties,
+            tags,
+        }
+    }
+
+    /// Get the client's window title.
+    pub fn name(&self) -> &str {
+        &self.properties.name
+    }
+
+    /// Get the set of tags attached to the client.
+    pub fn tags(&self) -> &HashSet<Tag> {
+        &self.tags
+    }
+
+    /// Get the client's last-configured geometry.
+    pub fn geometry(&self) -> &Geometry {
+        &self.geometry
+    }
+
+    /// Check whether the client's window is currently mapped on screen.
+    pub fn is_mapped(&self) -> bool {
+        self.mapped
+    }
+
+    /// Set whether the client's window is currently mapped, for windows adopted on startup.
+    pub fn set_mapped(&mut self, mapped: bool) {
+        self.mapped = mapped;
+    }
+
+    /// Check whether the client has been hidden (iconified).
+    pub fn is_iconic(&self) -> bool {
+        self.iconic
+    }
+}
+
 /// A unique identifier for tagsets, provided by the arena.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct TagSetId(u16);
 
+impl TagSetId {
+    /// Parse a tagset id from a single word.
+    pub fn from_word(word: &str) -> Option<TagSetId> {
+        u16::from_str(word).ok().map(TagSetId)
+    }
+}
+
 pub const DEFAULT_TAGSET: TagSetId = TagSetId(0);
 
 /// A tagset.
@@ -111,36 +356,293 @@ pub const DEFAULT_TAGSET: TagSetId = TagSetId(0);
 /// point in time.
 pub struct TagSet {
     /// The set of tags wrapped by the tagset.
+    ///
+    /// Clients are visible on the tagset if they carry any of these tags.
     tags: HashSet<Tag>,
+    /// The set of tags every client visible on the tagset has to carry.
+    ///
+    /// This is used to show the intersection of multiple tags.
+    required: HashSet<Tag>,
     /// The tag tree maintained by the layout on the given tagset.
     tree: TagTree,
-    /// The layout used.
-    layout: LayoutContainer,
+    /// The layouts the tagset can switch between, one of them being used.
+    layout: LayoutList,
+    /// The gaps left between and around the clients shown.
+    gaps: Gaps,
+    /// A snapshot of a tag tree that had to be altered to suit a layout switched to.
+    ///
+    /// It is restored by the next layout accepting it's shape, like the manual layout.
+    shadow: Option<ContainerState>,
+    /// Whether the tagset has been created by viewing a combination of tags.
+    ///
+    /// Transient tagsets are destroyed once they are left.
+    transient: bool,
 }
 
 impl TagSet {
-    // NB: assumes the tree is correct according to arena, tagset, and layout.
-    // TODO: pass a reference to the arena instead to compute the tree on-the-fly
-    pub fn new(tags: HashSet<Tag>, tree: TagTree, layout: LayoutContainer) -> TagSet {
-        TagSet {
+    /// Construct a new tagset, computing the tag tree from the clients given.
+    pub fn new(tags: HashSet<Tag>, layout: LayoutList, clients: &ClientSet) -> TagSet {
+        TagSet::with_required(tags, HashSet::new(), layout, clients)
+    }
+
+    /// Construct a new tagset showing only clients carrying all of the required tags.
+    pub fn with_required(tags: HashSet<Tag>,
+                         required: HashSet<Tag>,
+                         layout: LayoutList,
+                         clients: &ClientSet) -> TagSet {
+        let mut tagset = TagSet {
             tags,
-            tree,
+            required,
+            tree: TagTree::default(),
             layout,
+            gaps: Gaps::default(),
+            shadow: None,
+            transient: false,
+        };
+
+        tagset.sync_tree(clients);
+
+        tagset
+    }
+
+    /// Check whether a client is visible on the tagset.
+    ///
+    /// Hidden clients are never visible on any tagset.
+    pub fn shows(&self, client: &Client) -> bool {
+        !client.iconic && self.shows_tags(&client.tags)
+    }
+
+    /// Check whether a client with the given tags would be visible on the tagset.
+    ///
+    /// Clients on the scratchpad are never visible on any tagset.
+    pub fn shows_tags(&self, tags: &HashSet<Tag>) -> bool {
+        !tags.contains(&SCRATCHPAD) && !self.tags.is_disjoint(tags) &&
+            self.required.is_subset(tags)
+    }
+
+    /// Insert a client into the tag tree, using the layout, and focus it.
+    fn insert_client(&mut self, client: ClientId) {
+        let id = self.tree.alloc(Container::Client(ClientContainer::new(client)));
+        self.layout.as_layout().insert_container(&mut self.tree, id);
+        self.tree.focus(id);
+    }
+
+    /// Remove a client from the tag tree, using the layout.
+    fn remove_client(&mut self, id: ContainerId) {
+        self.layout.as_layout().delete_container(&mut self.tree, id);
+        self.tree.free(id);
+        self.tree.refocus();
+    }
+
+    /// Update the tag tree after a client's tags have changed.
+    ///
+    /// Clients already in the tree, like clients exchanged in place, aren't inserted again.
+    /// Returns `true` if the client gained or lost visibility on the tagset.
+    fn update_client(&mut self, client: ClientId, old: &HashSet<Tag>, new: &HashSet<Tag>)
+        -> bool
+    {
+        let was_shown = self.shows_tags(old);
+        let is_shown = self.shows_tags(new);
+
+        if was_shown == is_shown {
+            return false;
+        }
+
+        if is_shown {
+            if self.tree.find_client(client).is_some() {
+                return false;
+            }
+
+            self.insert_client(client);
+        } else if let Some(id) = self.tree.find_client(client) {
+            self.remove_client(id);
+        }
+
+        self.layout.as_layout().correct_tree(&mut self.tree);
+
+        true
+    }
+
+    /// Move a container next to another container in the tag tree, using the layout.
+    ///
+    /// Returns `false` if either container contains the other, or if the other container is the
+    /// root. Moving a container next to one of it's ancestors would free the ancestor when the
+    /// container is deleted, leaving it nowhere to attach to.
+    fn move_next_to(&mut self, id: ContainerId, target: ContainerId) -> bool {
+        if self.tree.is_ancestor(id, target) || self.tree.is_ancestor(target, id) ||
+            self.tree.parent(target).is_none() {
+            return false;
+        }
+
+        self.layout.as_layout().delete_container(&mut self.tree, id);
+        self.tree.detach(id);
+        self.tree.attach_after(id, target);
+
+        if let Some(focused) = self.tree.focused {
+            self.tree.focus(focused);
+        }
+
+        self.layout.as_layout().correct_tree(&mut self.tree);
+
+        true
+    }
+
+    /// Switch layouts by applying an operation to the list of layouts.
+    ///
+    /// If the tag tree has been altered when switching to an earlier layout, and the new layout
+    /// accepts it's original shape, it is restored. Otherwise, the tree is corrected, keeping
+    /// a snapshot of it's shape if it had to be altered. Returns `false` if the operation
+    /// didn't switch layouts.
+    fn switch_layout<F: FnOnce(&mut LayoutList) -> bool>(&mut self, switch: F) -> bool {
+        if !switch(&mut self.layout) {
+            return false;
+        }
+
+        let shadow = self.shadow.take();
+        let restored = shadow.as_ref().map(|s| self.tree.reshaped(s.clone()));
+
+        match restored {
+            Some((tree, missing)) if self.layout.as_layout().check_tree(&tree) => {
+                let old = mem::replace(&mut self.tree, tree);
+
+                for client in missing {
+                    self.insert_client(client);
+
+                    if let (Some(old_id), Some(id)) =
+                        (old.find_client(client), self.tree.find_client(client)) {
+                        for mark in old.marks_of(old_id) {
+                            self.tree.set_mark(mark.to_owned(), id);
+                        }
+                    }
+                }
+
+                let focused = old
+                    .focused
+                    .and_then(|f| old.get(f))
+                    .and_then(Container::as_client)
+                    .and_then(|c| self.tree.find_client(c.client));
+
+                if let Some(focused) = focused {
+                    self.tree.focus(focused);
+                }
+
+                self.layout.as_layout().correct_tree(&mut self.tree);
+            },
+            _ => {
+                self.shadow = shadow;
+
+                // keep the shape of the tree if the new layout alters it, weights included
+                let before = self.tree.snapshot(self.tree.root, DEFAULT_WEIGHT);
+                self.layout.as_layout().correct_tree(&mut self.tree);
+
+                if self.shadow.is_none() &&
+                    self.tree.snapshot(self.tree.root, DEFAULT_WEIGHT) != before
+                {
+                    self.shadow = Some(before);
+                }
+            },
+        }
+
+        true
+    }
+
+    /// Bring the tag tree in sync with the set of clients visible on the tagset.
+    ///
+    /// Clients that are no longer visible are removed from the tree, clients that became visible
+    /// are inserted using the layout. The arrangement of all other clients is preserved.
+    pub fn sync_tree(&mut self, clients: &ClientSet) {
+        let present = self.tree.clients();
+
+        for &(id, client) in &present {
+            if !clients.get(&client).map(|c| self.shows(c)).unwrap_or(false) {
+                self.remove_client(id);
+            }
+        }
+
+        let mut gained: Vec<ClientId> = clients
+            .values()
+            .filter(|c| self.shows(c) && !present.iter().any(|&(_, p)| p == c.window))
+            .map(|c| c.window)
+            .collect();
+        gained.sort();
+
+        for client in gained {
+            self.insert_client(client);
         }
+
+        self.layout.as_layout().correct_tree(&mut self.tree);
+    }
+
+    /// Get the set of tags wrapped by the tagset.
+    pub fn tags(&self) -> &HashSet<Tag> {
+        &self.tags
+    }
+
+    /// Get the set of tags every client visible on the tagset has to carry.
+    pub fn required(&self) -> &HashSet<Tag> {
+        &self.required
+    }
+
+    /// Get a reference to the tag tree of the tagset.
+    pub fn tree(&self) -> &TagTree {
+        &self.tree
+    }
+
+    /// Get a reference to the layout used by the tagset.
+    pub fn layout(&self) -> &LayoutContainer {
+        self.layout.current()
+    }
+
+    /// Get a mutable reference to the layout used by the tagset.
+    pub fn layout_mut(&mut self) -> &mut LayoutContainer {
+        self.layout.current_mut()
+    }
+
+    /// Get a reference to the list of layouts the tagset can switch between.
+    pub fn layouts(&self) -> &LayoutList {
+        &self.layout
+    }
+
+    /// Get the gaps of the tagset.
+    pub fn gaps(&self) -> Gaps {
+        self.gaps
+    }
+
+    /// Get a mutable reference to the gaps of the tagset.
+    pub fn gaps_mut(&mut self) -> &mut Gaps {
+        &mut self.gaps
     }
 }
 
+/// The time a spawned scratchpad client is waited for before it isn't shown anymore.
+pub const SCRATCHPAD_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// A unique identifier for screens, provided by the arena.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct ScreenId(u8);
 
+impl ScreenId {
+    /// Parse a screen id from a single word.
+    pub fn from_word(word: &str) -> Option<ScreenId> {
+        u8::from_str(word).ok().map(ScreenId)
+    }
+}
+
 pub const DEFAULT_SCREEN: ScreenId = ScreenId(0);
 
+/// The number of tagsets a screen remembers having shown.
+pub const SCREEN_HISTORY_SIZE: usize = 16;
+
 /// A screen showing a tagset.
 pub struct Screen {
     /// The screen's geometry.
     geometry: Geometry,
     /// The tagset currently shown on the screen.
     tagset: TagSetId,
+    /// The tagsets previously shown on the screen, the most recent one last.
+    history: VecDeque<TagSetId>,
+    /// The tagsets left by going back in the history, the most recent one last.
+    future: Vec<TagSetId>,
 }
 
 impl Screen {
@@ -148,22 +650,77 @@ impl Screen {
         Screen {
             geometry,
             tagset,
+            history: VecDeque::with_capacity(SCREEN_HISTORY_SIZE),
+            future: Vec::new(),
+        }
+    }
+
+    /// Remember a tagset as previously shown on the screen.
+    fn push_history(&mut self, id: TagSetId) {
+        if self.history.back() == Some(&id) {
+            return;
+        }
+
+        if self.history.len() == SCREEN_HISTORY_SIZE {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(id);
+    }
+
+    /// Show a tagset on the screen, remembering the tagset shown previously.
+    ///
+    /// This starts a new branch of history, so the tagsets left by going back are forgotten.
+    fn show(&mut self, id: TagSetId) {
+        if self.tagset != id {
+            let previous = self.tagset;
+            self.push_history(previous);
+            self.future.clear();
+            self.tagset = id;
         }
     }
+
+    /// Get the screen's geometry.
+    pub fn geometry(&self) -> &Geometry {
+        &self.geometry
+    }
+
+    /// Get the tagset currently shown on the screen.
+    pub fn tagset(&self) -> TagSetId {
+        self.tagset
+    }
 }
 
 /// A split type used in a container.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SplitType {
     /// A horizontal split.
+    ///
+    /// The children are placed next to each other, from left to right.
     Horizontal,
     /// A vertical split.
+    ///
+    /// The children are placed on top of each other, from top to bottom.
     Vertical,
     /// A tabbed "split".
     ///
-    /// This is basically a container that allows to tab through multiple clients.
+    /// This is basically a container that allows to tab through multiple clients. Only the
+    /// last-focused child is shown, using the whole area of the container.
     Tabbed,
 }
 
+impl SplitType {
+    /// Parse a split type from a single word.
+    pub fn from_word(word: &str) -> Option<SplitType> {
+        match word {
+            "horizontal" => Some(SplitType::Horizontal),
+            "vertical" => Some(SplitType::Vertical),
+            "tabbed" => Some(SplitType::Tabbed),
+            _ => None,
+        }
+    }
+}
+
 impl Default for SplitType {
     fn default() -> Self {
         SplitType::Vertical
@@ -171,23 +728,77 @@ impl Default for SplitType {
 }
 
 /// A unique indentifier for containers, provided by the tag tree they are located in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct ContainerId(u16);
 
 pub const DEFAULT_CONTAINER: ContainerId = ContainerId(0);
 
+/// The weight given to children of split containers by default.
+pub const DEFAULT_WEIGHT: u32 = 100;
+
 /// A container representing an inner node in a tag tree.
 #[derive(Default)]
 pub struct SplitContainer {
     /// The split type of the container.
     split_type: SplitType,
     /// The child container last focused.
-    last_focused: Option<u16>,
+    last_focused: Option<ContainerId>,
     /// The ordered set of child containers.
-    children: Vec<u16>,
+    children: Vec<ContainerId>,
+    /// The weights of the child containers, in the same order.
+    ///
+    /// Each child gets a share of the container's size proportional to it's weight.
+    weights: Vec<u32>,
     /// Whether the container is marked floating.
     floating: bool,
 }
 
+impl SplitContainer {
+    /// Get the split type of the container.
+    pub fn split_type(&self) -> SplitType {
+        self.split_type
+    }
+
+    /// Get the child container last focused.
+    pub fn last_focused(&self) -> Option<ContainerId> {
+        self.last_focused
+    }
+
+    /// Get the ordered set of child containers.
+    pub fn children(&self) -> &[ContainerId] {
+        &self.children
+    }
+
+    /// Get the weights of the child containers, in the same order.
+    pub fn weights(&self) -> &[u32] {
+        &self.weights
+    }
+
+    /// Insert a child container at the given position, using the default weight.
+    ///
+    /// The weights of all other children are preserved.
+    pub fn insert_child(&mut self, index: usize, child: ContainerId) {
+        self.children.insert(index, child);
+        self.weights.insert(index, DEFAULT_WEIGHT);
+    }
+
+    /// Remove a child container, if present, and return it's former position.
+    ///
+    /// The weights of all other children are preserved.
+    pub fn remove_child(&mut self, child: ContainerId) -> Option<usize> {
+        let index = self.children.iter().position(|&c| c == child)?;
+
+        self.children.remove(index);
+        self.weights.remove(index);
+
+        if self.last_focused == Some(child) {
+            self.last_focused = None;
+        }
+
+        Some(index)
+    }
+}
+
 /// A container representing a leaf in a tag tree.
 pub struct ClientContainer {
     /// The client in the container.
@@ -196,6 +807,20 @@ pub struct ClientContainer {
     floating: bool,
 }
 
+impl ClientContainer {
+    pub fn new(client: ClientId) -> ClientContainer {
+        ClientContainer {
+            client,
+            floating: false,
+        }
+    }
+
+    /// Get the client in the container.
+    pub fn client(&self) -> ClientId {
+        self.client
+    }
+}
+
 /// A container representing an arbitrary node in a tag tree.
 pub enum Container {
     /// A split container.
@@ -204,6 +829,32 @@ pub enum Container {
     Client(ClientContainer),
 }
 
+impl Container {
+    /// Get a reference to the split container wrapped, if any.
+    pub fn as_split(&self) -> Option<&SplitContainer> {
+        match *self {
+            Container::Split(ref s) => Some(s),
+            Container::Client(_) => None,
+        }
+    }
+
+    /// Get a reference to the client container wrapped, if any.
+    pub fn as_client(&self) -> Option<&ClientContainer> {
+        match *self {
+            Container::Split(_) => None,
+            Container::Client(ref c) => Some(c),
+        }
+    }
+
+    /// Check whether the container is marked floating.
+    pub fn is_floating(&self) -> bool {
+        match *self {
+            Container::Split(ref s) => s.floating,
+            Container::Client(ref c) => c.floating,
+        }
+    }
+}
+
 impl Default for Container {
     fn default() -> Self {
         Container::Split(SplitContainer::default())
@@ -216,49 +867,2892 @@ impl Default for Container {
 /// structured according to a layout. Always contains a root node.
 pub struct TagTree {
     /// An arena of containers.
-    container_arena: Vec<Container>,
+    ///
+    /// Freed containers leave a `None` entry, and their ids get reused by new containers.
+    container_arena: Vec<Option<Container>>,
     /// The root container of the tree.
     root: ContainerId,
     /// The focused container in the tree.
     focused: Option<ContainerId>,
     /// The selected container in the tree.
     selected: Option<ContainerId>,
+    /// The marked containers in the tree, indexed by mark.
+    ///
+    /// Marks are unique across all tag trees, and a container can carry multiple marks.
+    marks: BTreeMap<String, ContainerId>,
 }
 
 impl Default for TagTree {
     fn default() -> Self {
         TagTree {
-            container_arena: vec![Container::default()],
+            container_arena: vec![Some(Container::default())],
             root: DEFAULT_CONTAINER,
             focused: None,
             selected: None,
+            marks: BTreeMap::new(),
         }
     }
 }
 
-/// The type of the set of clients.
-pub type ClientSet = HashMap<ClientId, Client>;
+impl TagTree {
+    /// Get the root container of the tree.
+    pub fn root(&self) -> ContainerId {
+        self.root
+    }
 
-/// The toplevel structure keeping track of clients, tagsets, and screens.
-///
-/// Always contains at least one screen and at least one tagset.
-pub struct Arena {
-    /// The set of clients, indexed by values of type `ClientId`.
-    clients: ClientSet,
-    /// The set of tagsets, indexed by values of type `TagSetId`.
-    tagsets: Vec<TagSet>,
-    /// The set of screens, indexed by values of type `ScreenId`.
-    screens: Vec<Screen>,
-}
+    /// Get the focused container of the tree.
+    pub fn focused(&self) -> Option<ContainerId> {
+        self.focused
+    }
 
-impl Arena {
-    pub fn new(default_tagset: HashSet<Tag>,
-               default_layout: LayoutContainer,
-               default_screen_geometry: Geometry) -> Arena {
-        Arena {
-            clients: ClientSet::default(),
-            tagsets: vec![TagSet::new(default_tagset, TagTree::default(), default_layout)],
-            screens: vec![Screen::new(default_screen_geometry, DEFAULT_TAGSET)],
+    /// Get the selected container of the tree.
+    pub fn selected(&self) -> Option<ContainerId> {
+        self.selected
+    }
+
+    /// Select the focused container, or clear the selection if it is already selected.
+    pub fn toggle_selection(&mut self) -> bool {
+        if self.focused.is_none() {
+            return false;
+        }
+
+        self.selected = if self.selected == self.focused { None } else { self.focused };
+
+        true
+    }
+
+    /// Get the container carrying a mark, if any.
+    pub fn marked(&self, mark: &str) -> Option<ContainerId> {
+        self.marks.get(mark).cloned()
+    }
+
+    /// Get the marks carried by a container, in order.
+    pub fn marks_of(&self, id: ContainerId) -> Vec<&str> {
+        self.marks.iter().filter(|&(_, &c)| c == id).map(|(m, _)| m.as_str()).collect()
+    }
+
+    /// Get all marks in the tree, in order, along with the containers carrying them.
+    pub fn marks(&self) -> &BTreeMap<String, ContainerId> {
+        &self.marks
+    }
+
+    /// Attach a mark to a container, replacing the container previously carrying it.
+    pub fn set_mark(&mut self, mark: String, id: ContainerId) {
+        self.marks.insert(mark, id);
+    }
+
+    /// Remove a mark and signify whether it was present.
+    pub fn unset_mark(&mut self, mark: &str) -> bool {
+        self.marks.remove(mark).is_some()
+    }
+
+    /// Remove all marks carried by a container, and return them.
+    pub fn take_marks(&mut self, id: ContainerId) -> Vec<String> {
+        let marks: Vec<String> = self.marks_of(id).into_iter().map(str::to_owned).collect();
+
+        for mark in &marks {
+            self.marks.remove(mark);
         }
+
+        marks
+    }
+
+    /// Replace the client in a leaf container, returning the previous one.
+    ///
+    /// Returns `None` if the container is not a leaf.
+    fn replace_client(&mut self, id: ContainerId, client: ClientId) -> Option<ClientId> {
+        match self.container_arena.get_mut(id.0 as usize).and_then(Option::as_mut) {
+            Some(&mut Container::Client(ref mut c)) => Some(mem::replace(&mut c.client, client)),
+            _ => None,
+        }
+    }
+
+    /// Get a reference to a container in the tree.
+    pub fn get(&self, id: ContainerId) -> Option<&Container> {
+        self.container_arena.get(id.0 as usize).and_then(Option::as_ref)
+    }
+
+    /// Get a reference to a split container in the tree.
+    pub fn get_split(&self, id: ContainerId) -> Option<&SplitContainer> {
+        self.get(id).and_then(Container::as_split)
+    }
+
+    /// Get a mutable reference to a split container in the tree.
+    fn get_split_mut(&mut self, id: ContainerId) -> Option<&mut SplitContainer> {
+        match self.container_arena.get_mut(id.0 as usize).and_then(Option::as_mut) {
+            Some(container) => match *container {
+                Container::Split(ref mut s) => Some(s),
+                Container::Client(_) => None,
+            },
+            None => None,
+        }
+    }
+
+    /// Get the parent of a container, if any.
+    pub fn parent(&self, id: ContainerId) -> Option<ContainerId> {
+        self.container_arena
+            .iter()
+            .position(|c| match *c {
+                Some(Container::Split(ref s)) => s.children.contains(&id),
+                _ => false,
+            })
+            .map(|i| ContainerId(i as u16))
+    }
+
+    /// Allocate a new container in the tree, without attaching it to any parent.
+    ///
+    /// The smallest id not in use by another container is allocated.
+    pub fn alloc(&mut self, container: Container) -> ContainerId {
+        if let Some(index) = self.container_arena.iter().position(Option::is_none) {
+            self.container_arena[index] = Some(container);
+            ContainerId(index as u16)
+        } else {
+            self.container_arena.push(Some(container));
+            ContainerId(self.container_arena.len() as u16 - 1)
+        }
+    }
+
+    /// Free a container and all of it's descendants.
+    ///
+    /// The container is detached from it's parent first, if necessary. The root container can't
+    /// be freed.
+    pub fn free(&mut self, id: ContainerId) {
+        if id == self.root {
+            return;
+        }
+
+        if let Some(parent) = self.parent(id) {
+            self.get_split_mut(parent).unwrap().remove_child(id);
+        }
+
+        let container = self.container_arena.get_mut(id.0 as usize).and_then(Option::take);
+
+        if let Some(Container::Split(split)) = container {
+            for child in split.children {
+                self.free(child);
+            }
+        }
+
+        if self.focused == Some(id) {
+            self.focused = None;
+        }
+
+        if self.selected == Some(id) {
+            self.selected = None;
+        }
+
+        self.marks.retain(|_, &mut c| c != id);
+    }
+
+    /// Check whether a container is an ancestor of another container, or the same container.
+    pub fn is_ancestor(&self, ancestor: ContainerId, mut id: ContainerId) -> bool {
+        loop {
+            if id == ancestor {
+                return true;
+            }
+
+            match self.parent(id) {
+                Some(parent) => id = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// Detach a container from it's parent, if any.
+    pub fn detach(&mut self, id: ContainerId) {
+        if let Some(parent) = self.parent(id) {
+            self.get_split_mut(parent).unwrap().remove_child(id);
+        }
+    }
+
+    /// Attach a detached container to the parent of another container, right after it.
+    ///
+    /// Returns `false` if the other container has no parent.
+    pub fn attach_after(&mut self, id: ContainerId, target: ContainerId) -> bool {
+        let parent = match self.parent(target) {
+            Some(parent) => parent,
+            None => return false,
+        };
+
+        let split = self.get_split_mut(parent).unwrap();
+        let index = split.children.iter().position(|&c| c == target).unwrap();
+        split.insert_child(index + 1, id);
+
+        true
+    }
+
+    /// Attach a detached container as the last child of a split container.
+    ///
+    /// Returns `false` if the parent is not a split container.
+    pub fn append_child(&mut self, parent: ContainerId, id: ContainerId) -> bool {
+        let index = match self.get_split(parent) {
+            Some(split) => split.children.len(),
+            None => return false,
+        };
+
+        self.attach_at(parent, index, id)
+    }
+
+    /// Attach a detached container as a child of a split container, at the given position.
+    ///
+    /// Returns `false` if the parent is not a split container.
+    pub fn attach_at(&mut self, parent: ContainerId, index: usize, id: ContainerId) -> bool {
+        match self.get_split_mut(parent) {
+            Some(split) => {
+                let index = index.min(split.children.len());
+                split.insert_child(index, id);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Check whether the tree is flat, that is, all children of the root are clients.
+    pub fn is_flat(&self) -> bool {
+        let is_client = |c: &ContainerId| self.get(*c).and_then(Container::as_client).is_some();
+        self.get_split(self.root).map(|s| s.children.iter().all(is_client)).unwrap_or(false)
+    }
+
+    /// Make the tree flat, keeping all clients in order as children of the root.
+    ///
+    /// All split containers besides the root are freed, and the weights are reset.
+    pub fn flatten(&mut self) {
+        let root = self.root;
+
+        if self.is_flat() {
+            self.equalize(root);
+            return;
+        }
+
+        let clients: Vec<ContainerId> = self
+            .leaves(root)
+            .into_iter()
+            .filter(|&c| self.get(c).and_then(Container::as_client).is_some())
+            .collect();
+
+        for &client in &clients {
+            self.detach(client);
+        }
+
+        for child in self.get_split(root).unwrap().children.clone() {
+            self.free(child);
+        }
+
+        for client in clients {
+            self.append_child(root, client);
+        }
+
+        match self.focused {
+            Some(focused) => self.focus(focused),
+            None => self.refocus(),
+        }
+    }
+
+    /// Get all leaves of a subtree, in order.
+    pub fn leaves(&self, id: ContainerId) -> Vec<ContainerId> {
+        match self.get_split(id) {
+            Some(s) => s.children.iter().flat_map(|&c| self.leaves(c)).collect(),
+            None => vec![id],
+        }
+    }
+
+    /// Get all containers of a subtree in pre-order, starting with the subtree's root.
+    pub fn preorder(&self, id: ContainerId) -> Vec<ContainerId> {
+        let mut containers = vec![id];
+
+        if let Some(s) = self.get_split(id) {
+            for &child in &s.children {
+                containers.extend(self.preorder(child));
+            }
+        }
+
+        containers
+    }
+
+    /// Swap the positions of two containers.
+    ///
+    /// The weights stay with the positions, so the sizes of the areas swapped don't change.
+    /// Returns `false` if one of the containers contains the other, or is the root.
+    pub fn swap(&mut self, a: ContainerId, b: ContainerId) -> bool {
+        if self.is_ancestor(a, b) || self.is_ancestor(b, a) {
+            return false;
+        }
+
+        let (parent_a, parent_b) = match (self.parent(a), self.parent(b)) {
+            (Some(parent_a), Some(parent_b)) => (parent_a, parent_b),
+            _ => return false,
+        };
+
+        let position = |tree: &TagTree, parent, child| {
+            tree.get_split(parent).unwrap().children.iter().position(|&c| c == child).unwrap()
+        };
+        let index_a = position(self, parent_a, a);
+        let index_b = position(self, parent_b, b);
+
+        for &(parent, index, old, new) in &[(parent_a, index_a, a, b), (parent_b, index_b, b, a)] {
+            let split = self.get_split_mut(parent).unwrap();
+            split.children[index] = new;
+
+            if parent_a != parent_b && split.last_focused == Some(old) {
+                split.last_focused = Some(new);
+            }
+        }
+
+        if let Some(focused) = self.focused {
+            self.focus(focused);
+        }
+
+        true
+    }
+
+    /// Get a snapshot of a subtree, given the weight of it's root in it's parent.
+    fn snapshot(&self, id: ContainerId, weight: u32) -> ContainerState {
+        let container = self.get(id).unwrap();
+
+        let kind = match *container {
+            Container::Split(ref s) => {
+                let last_focused =
+                    s.last_focused.and_then(|l| s.children.iter().position(|&c| c == l));
+                let children = s.children
+                    .iter()
+                    .zip(&s.weights)
+                    .map(|(&c, &w)| self.snapshot(c, w))
+                    .collect();
+
+                ContainerKind::Split(s.split_type, last_focused, children)
+            },
+            Container::Client(ref c) => ContainerKind::Client(c.client),
+        };
+
+        ContainerState {
+            kind,
+            weight,
+            floating: container.is_floating(),
+            focused: self.focused == Some(id),
+            marks: self.marks_of(id).into_iter().map(str::to_owned).collect(),
+        }
+    }
+
+    /// Rebuild a tag tree from a snapshot, keeping only the clients present in a client set.
+    fn from_snapshot(state: ContainerState, clients: &ClientSet) -> TagTree {
+        TagTree::rebuild(state, &|c| clients.contains_key(&c))
+    }
+
+    /// Rebuild a tag tree from a snapshot, keeping only the clients satisfying a predicate.
+    fn rebuild<F: Fn(ClientId) -> bool>(state: ContainerState, keep: &F) -> TagTree {
+        let mut tree = TagTree::default();
+        let root = tree.root;
+
+        if let ContainerKind::Split(split_type, last_focused, children) = state.kind {
+            tree.get_split_mut(root).unwrap().split_type = split_type;
+            tree.restore_children(root, last_focused, children, keep);
+        }
+
+        if state.focused {
+            tree.focused = Some(root);
+        }
+
+        for mark in state.marks {
+            tree.marks.insert(mark, root);
+        }
+
+        match tree.focused {
+            Some(focused) => tree.focus(focused),
+            None => tree.refocus(),
+        }
+
+        tree
+    }
+
+    /// Rebuild the children of a split container from their snapshots.
+    fn restore_children<F>(&mut self,
+                           id: ContainerId,
+                           last_focused: Option<usize>,
+                           children: Vec<ContainerState>,
+                           keep: &F)
+        where F: Fn(ClientId) -> bool
+    {
+        for (i, child) in children.into_iter().enumerate() {
+            // resizing relies on all weights being positive
+            let weight = child.weight.max(1);
+
+            if let Some(child) = self.restore(child, keep) {
+                let split = self.get_split_mut(id).unwrap();
+                split.children.push(child);
+                split.weights.push(weight);
+
+                if last_focused == Some(i) {
+                    split.last_focused = Some(child);
+                }
+            }
+        }
+    }
+
+    /// Rebuild a subtree from a snapshot, keeping only the clients satisfying a predicate.
+    ///
+    /// Split containers left empty are dropped. Returns the id of the subtree's root, if any.
+    fn restore<F>(&mut self, state: ContainerState, keep: &F) -> Option<ContainerId>
+        where F: Fn(ClientId) -> bool
+    {
+        let id = match state.kind {
+            ContainerKind::Split(split_type, last_focused, children) => {
+                let id = self.alloc(Container::Split(SplitContainer {
+                    split_type,
+                    floating: state.floating,
+                    ..SplitContainer::default()
+                }));
+
+                self.restore_children(id, last_focused, children, keep);
+
+                if self.get_split(id).unwrap().children.is_empty() {
+                    self.container_arena[id.0 as usize] = None;
+                    return None;
+                }
+
+                id
+            },
+            ContainerKind::Client(client) if keep(client) => {
+                self.alloc(Container::Client(ClientContainer {
+                    client,
+                    floating: state.floating,
+                }))
+            },
+            ContainerKind::Client(_) => return None,
+        };
+
+        if state.focused {
+            self.focused = Some(id);
+        }
+
+        for mark in state.marks {
+            self.marks.insert(mark, id);
+        }
+
+        Some(id)
+    }
+
+    /// Build a tree in the shape of a snapshot taken earlier, holding the clients of this tree.
+    ///
+    /// Clients keep their marks and the focus, while marks on split containers are taken from
+    /// the snapshot. Returns the new tree, along with the clients missing from the snapshot.
+    pub fn reshaped(&self, state: ContainerState) -> (TagTree, Vec<ClientId>) {
+        let present = self.clients();
+        let mut tree = TagTree::rebuild(state, &|c| present.iter().any(|&(_, p)| p == c));
+
+        {
+            let arena = &tree.container_arena;
+            tree.marks.retain(|_, id| {
+                arena[id.0 as usize].as_ref().and_then(Container::as_client).is_none()
+            });
+        }
+
+        let mut missing = Vec::new();
+
+        for &(id, client) in &present {
+            match tree.find_client(client) {
+                Some(new_id) => for mark in self.marks_of(id) {
+                    tree.marks.insert(mark.to_owned(), new_id);
+                },
+                None => missing.push(client),
+            }
+        }
+
+        let focused = self
+            .focused
+            .and_then(|f| self.get(f))
+            .and_then(Container::as_client)
+            .and_then(|c| tree.find_client(c.client));
+
+        match focused {
+            Some(focused) => tree.focus(focused),
+            None => tree.refocus(),
+        }
+
+        (tree, missing)
+    }
+
+    /// Get all clients in the tree, along with the containers holding them.
+    pub fn clients(&self) -> Vec<(ContainerId, ClientId)> {
+        self.container_arena
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| match *c {
+                Some(Container::Client(ref c)) => Some((ContainerId(i as u16), c.client)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get all clients in a subtree, in order.
+    pub fn subtree_clients(&self, id: ContainerId) -> Vec<ClientId> {
+        match self.get(id) {
+            Some(container) => match *container {
+                Container::Split(ref s) => {
+                    s.children.iter().flat_map(|&c| self.subtree_clients(c)).collect()
+                },
+                Container::Client(ref c) => vec![c.client],
+            },
+            None => Vec::new(),
+        }
+    }
+
+    /// Find the container holding a client.
+    pub fn find_client(&self, client: ClientId) -> Option<ContainerId> {
+        self.clients().into_iter().find(|&(_, c)| c == client).map(|(id, _)| id)
+    }
+
+    /// Find the leaf to focus when entering a subtree, following the last-focused children.
+    pub fn focused_leaf(&self, mut id: ContainerId) -> ContainerId {
+        while let Some(s) = self.get_split(id) {
+            match s.last_focused.or_else(|| s.children.first().cloned()) {
+                Some(child) => id = child,
+                None => break,
+            }
+        }
+
+        id
+    }
+
+    /// Make sure some leaf is focused, if the tree contains any containers besides the root.
+    pub fn refocus(&mut self) {
+        if self.focused.is_some() {
+            return;
+        }
+
+        let leaf = self.focused_leaf(self.root);
+
+        if leaf != self.root {
+            self.focus(leaf);
+        }
+    }
+
+    /// Focus a container, updating the last-focused children along the path to the root.
+    pub fn focus(&mut self, id: ContainerId) {
+        let mut child = id;
+
+        while let Some(parent) = self.parent(child) {
+            if let Some(s) = self.get_split_mut(parent) {
+                s.last_focused = Some(child);
+            }
+
+            child = parent;
+        }
+
+        self.focused = Some(id);
+    }
+
+    /// Get the tabbed container closest to the focused container, if any.
+    ///
+    /// Returns the tabbed container's id and the index of the active tab.
+    pub fn focused_tabs(&self) -> Option<(ContainerId, usize)> {
+        let mut child = self.focused?;
+
+        while let Some(parent) = self.parent(child) {
+            if let Some(s) = self.get_split(parent) {
+                if s.split_type == SplitType::Tabbed {
+                    let index = s.children.iter().position(|&c| c == child).unwrap_or(0);
+                    return Some((parent, index));
+                }
+            }
+
+            child = parent;
+        }
+
+        None
+    }
+
+    /// Focus the next or previous tab in the tabbed container closest to the focused container.
+    ///
+    /// Returns `false` if no such tabbed container exists.
+    pub fn cycle_tab(&mut self, forward: bool) -> bool {
+        let next = match self.focused_tabs() {
+            Some((tabbed, index)) => {
+                let children = &self.get_split(tabbed).unwrap().children;
+                let n = children.len();
+
+                if forward {
+                    children[(index + 1) % n]
+                } else {
+                    children[(index + n - 1) % n]
+                }
+            },
+            None => return false,
+        };
+
+        let leaf = self.focused_leaf(next);
+        self.focus(leaf);
+
+        true
+    }
+
+    /// Split a container, so that containers inserted next to it share a split of the given type.
+    ///
+    /// The container is wrapped in a new split container taking it's place, unless it is the
+    /// only child of it's parent, in which case the parent's split type is changed. Splitting
+    /// the root changes it's split type.
+    pub fn split(&mut self, id: ContainerId, split_type: SplitType) -> bool {
+        let parent = match self.parent(id) {
+            Some(parent) => parent,
+            None => return match self.get_split_mut(id) {
+                Some(split) => {
+                    split.split_type = split_type;
+                    true
+                },
+                None => false,
+            },
+        };
+
+        if self.get_split(parent).unwrap().children.len() == 1 {
+            self.get_split_mut(parent).unwrap().split_type = split_type;
+            return true;
+        }
+
+        let wrapper = self.alloc(Container::Split(SplitContainer {
+            split_type,
+            last_focused: Some(id),
+            children: vec![id],
+            weights: vec![DEFAULT_WEIGHT],
+            floating: false,
+        }));
+
+        let split = self.get_split_mut(parent).unwrap();
+        let index = split.children.iter().position(|&c| c == id).unwrap();
+        split.children[index] = wrapper;
+
+        if split.last_focused == Some(id) {
+            split.last_focused = Some(wrapper);
+        }
+
+        true
+    }
+
+    /// Toggle the split type of a container's parent between horizontal and vertical.
+    ///
+    /// Tabbed containers become horizontal splits. Returns `false` if the container is the root.
+    pub fn toggle_split(&mut self, id: ContainerId) -> bool {
+        let split = match self.parent(id) {
+            Some(parent) => self.get_split_mut(parent).unwrap(),
+            None => return false,
+        };
+
+        split.split_type = match split.split_type {
+            SplitType::Horizontal => SplitType::Vertical,
+            SplitType::Vertical | SplitType::Tabbed => SplitType::Horizontal,
+        };
+
+        true
+    }
+
+    /// Grow (or shrink, for negative amounts) the focused container in a geometric direction.
+    ///
+    /// The closest ancestor split along the direction with a tiled neighbour in that direction
+    /// is used, and weight is transferred between the focused branch and that neighbour. No
+    /// weight drops below 1. Returns `false` if no such split exists.
+    pub fn resize(&mut self, direction: Direction, amount: i32) -> bool {
+        let split_type = match direction.split_type() {
+            Some(split_type) => split_type,
+            None => return false,
+        };
+        let forward = direction == Direction::Right || direction == Direction::Down;
+
+        let mut child = match self.focused {
+            Some(focused) => focused,
+            None => return false,
+        };
+
+        while let Some(parent) = self.parent(child) {
+            let found = self.get_split(parent).and_then(|s| {
+                if s.split_type != split_type {
+                    return None;
+                }
+
+                let index = s.children.iter().position(|&c| c == child)?;
+                let tiled = |&i: &usize| {
+                    self.get(s.children[i]).map(|c| !c.is_floating()).unwrap_or(false)
+                };
+
+                let neighbour = if forward {
+                    (index + 1..s.children.len()).find(&tiled)
+                } else {
+                    (0..index).rev().find(&tiled)
+                };
+
+                neighbour.map(|n| (index, n))
+            });
+
+            if let Some((index, neighbour)) = found {
+                let weights = &mut self.get_split_mut(parent).unwrap().weights;
+
+                if amount >= 0 {
+                    let delta = (amount as u32).min(weights[neighbour] - 1);
+                    weights[neighbour] -= delta;
+                    weights[index] = weights[index].saturating_add(delta);
+                } else {
+                    let delta = amount.unsigned_abs().min(weights[index] - 1);
+                    weights[index] -= delta;
+                    weights[neighbour] = weights[neighbour].saturating_add(delta);
+                }
+
+                return true;
+            }
+
+            child = parent;
+        }
+
+        false
+    }
+
+    /// Reset the weights of all split containers in a subtree to the default weight.
+    pub fn equalize(&mut self, id: ContainerId) {
+        let children = match self.get_split_mut(id) {
+            Some(s) => {
+                for weight in &mut s.weights {
+                    *weight = DEFAULT_WEIGHT;
+                }
+
+                s.children.clone()
+            },
+            None => return,
+        };
+
+        for child in children {
+            self.equalize(child);
+        }
+    }
+
+    /// Reset the weights of the subtree around the focused container.
+    ///
+    /// This affects the focused container's siblings, and all of their descendants.
+    pub fn equalize_focused(&mut self) -> bool {
+        match self.focused.map(|f| self.parent(f).unwrap_or(f)) {
+            Some(id) => {
+                self.equalize(id);
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+/// The type of the set of clients.
+pub type ClientSet = HashMap<ClientId, Client>;
+
+/// The toplevel structure keeping track of clients, tagsets, and screens.
+///
+/// Always contains at least one screen and at least one tagset.
+pub struct Arena {
+    /// The registry of all tags known.
+    tags: TagRegistry,
+    /// The set of clients, indexed by values of type `ClientId`.
+    clients: ClientSet,
+    /// The set of tagsets, indexed by values of type `TagSetId`.
+    ///
+    /// Destroyed tagsets leave a `None` entry, and their ids get reused by new tagsets.
+    tagsets: Vec<Option<TagSet>>,
+    /// The set of screens, indexed by values of type `ScreenId`.
+    screens: Vec<Screen>,
+    /// The screen currently focused.
+    focused_screen: ScreenId,
+    /// The policy used when showing a tagset shown on another screen.
+    view_policy: ViewPolicy,
+    /// The scratchpad configuration, if any.
+    scratchpad: Option<ScratchpadConfig>,
+    /// The scratchpad client currently shown, and the screen it is shown on.
+    scratchpad_shown: Option<(ClientId, ScreenId)>,
+    /// When the scratchpad command has been spawned, if it's client is to be shown as soon as
+    /// it appears.
+    scratchpad_pending: Option<Instant>,
+    /// The hidden clients, in the order they have been hidden.
+    iconified: Vec<ClientId>,
+    /// The gaps given to new tagsets.
+    gaps: Gaps,
+    /// The layouts given to new tagsets.
+    layouts: LayoutList,
+}
+
+/// The outcome of toggling the scratchpad.
+pub enum ScratchpadToggle {
+    /// The scratchpad client has been shown.
+    Shown,
+    /// The scratchpad client has been hidden.
+    Hidden,
+    /// No scratchpad client exists, and the given command is to be spawned.
+    Spawn(String),
+    /// No scratchpad client exists, and none can be spawned.
+    Missing,
+}
+
+impl Arena {
+    pub fn new(tags: TagRegistry,
+               default_tagset: HashSet<Tag>,
+               default_layouts: LayoutList,
+               default_screen_geometry: Geometry) -> Arena {
+        let default_tagset =
+            TagSet::new(default_tagset, default_layouts.clone(), &ClientSet::default());
+
+        Arena {
+            tags,
+            clients: ClientSet::default(),
+            tagsets: vec![Some(default_tagset)],
+            screens: vec![Screen::new(default_screen_geometry, DEFAULT_TAGSET)],
+            focused_screen: DEFAULT_SCREEN,
+            view_policy: ViewPolicy::default(),
+            scratchpad: None,
+            scratchpad_shown: None,
+            scratchpad_pending: None,
+            iconified: Vec::new(),
+            gaps: Gaps::default(),
+            layouts: default_layouts,
+        }
+    }
+
+    /// Set the policy used when showing a tagset shown on another screen.
+    pub fn set_view_policy(&mut self, view_policy: ViewPolicy) {
+        self.view_policy = view_policy;
+    }
+
+    /// Set the gaps given to new tagsets, and to all tagsets present.
+    pub fn set_gaps(&mut self, gaps: Gaps) {
+        self.gaps = gaps;
+
+        for tagset in self.tagsets.iter_mut().filter_map(Option::as_mut) {
+            tagset.gaps = gaps;
+        }
+    }
+
+    /// Set the scratchpad configuration.
+    pub fn set_scratchpad(&mut self, scratchpad: Option<ScratchpadConfig>) {
+        self.scratchpad = scratchpad;
+    }
+
+    /// Get the tags with the given names, creating tags that don't exist yet.
+    pub fn resolve_tags(&mut self, names: &[String]) -> HashSet<Tag> {
+        names.iter().map(|n| self.tags.intern(n)).collect()
+    }
+
+    /// Add a client to the arena, inserting it into the trees of all tagsets showing it.
+    ///
+    /// Clients matching the scratchpad's class are put on the scratchpad instead, and shown
+    /// right away if the scratchpad command has been spawned to create them.
+    pub fn add_client(&mut self, mut client: Client) {
+        let id = client.window;
+        let mut tags = mem::take(&mut client.tags);
+
+        let is_scratchpad = self
+            .scratchpad
+            .as_ref()
+            .map(|s| s.matches(&client.properties.class))
+            .unwrap_or(false);
+
+        if is_scratchpad {
+            tags = HashSet::new();
+            tags.insert(SCRATCHPAD);
+        }
+
+        self.clients.insert(id, client);
+        self.set_client_tags(id, tags);
+
+        if is_scratchpad {
+            // spawned clients taking too long aren't shown anymore
+            let pending = self.scratchpad_pending.take();
+
+            if pending.map(|t| t.elapsed() < SCRATCHPAD_TIMEOUT).unwrap_or(false) {
+                self.scratchpad_shown = Some((id, self.focused_screen));
+            }
+        }
+    }
+
+    /// Remove a client from the arena, removing it from the trees of all tagsets showing it.
+    pub fn remove_client(&mut self, id: ClientId) -> Option<Client> {
+        self.set_client_tags(id, HashSet::new());
+        self.iconified.retain(|&c| c != id);
+        self.clients.remove(&id)
+    }
+
+    /// Change the tags of a client, keeping the trees of all tagsets in sync.
+    ///
+    /// Every tagset that gains or loses visibility of the client has the client's container
+    /// inserted into or deleted from it's tree by it's layout. All changes to a client's tags
+    /// are expected to go through this method.
+    ///
+    /// Returns `true` if a tagset shown on a screen has been changed, so that the screens have
+    /// to be rendered again. The tags of hidden clients are changed without touching any tree.
+    pub fn set_client_tags(&mut self, id: ClientId, tags: HashSet<Tag>) -> bool {
+        let old = match self.clients.get_mut(&id) {
+            Some(client) if client.iconic => {
+                client.tags = tags;
+                return false;
+            },
+            Some(client) => mem::replace(&mut client.tags, tags),
+            None => return false,
+        };
+        let new = &self.clients[&id].tags;
+
+        let mut changed = false;
+
+        if !new.contains(&SCRATCHPAD) && self.scratchpad_client_shown() == Some(id) {
+            self.scratchpad_shown = None;
+            changed = true;
+        }
+
+        for (i, tagset) in self.tagsets.iter_mut().enumerate() {
+            if let Some(ref mut tagset) = *tagset {
+                let visible = self.screens.iter().any(|s| s.tagset == TagSetId(i as u16));
+
+                if tagset.update_client(id, &old, new) && visible {
+                    changed = true;
+                }
+            }
+        }
+
+        changed
+    }
+
+    /// Change the window title of a client.
+    ///
+    /// Returns `true` if the client exists and it's title has changed.
+    pub fn set_client_name(&mut self, id: ClientId, name: String) -> bool {
+        match self.clients.get_mut(&id) {
+            Some(client) if client.properties.name != name => {
+                client.properties.name = name;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Note that we have requested a client's window to be unmapped.
+    pub fn expect_unmap(&mut self, id: ClientId) {
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.pending_unmaps += 1;
+        }
+    }
+
+    /// Account for an `UnmapNotify` of a client's window.
+    ///
+    /// Returns `true` if the window has been unmapped by us, and `false` if the client has
+    /// withdrawn it, or doesn't exist.
+    pub fn unmap_expected(&mut self, id: ClientId) -> bool {
+        match self.clients.get_mut(&id) {
+            Some(client) if client.pending_unmaps > 0 => {
+                client.pending_unmaps -= 1;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Get a reference to a client, if it exists.
+    pub fn client(&self, id: ClientId) -> Option<&Client> {
+        self.clients.get(&id)
+    }
+
+    /// Get a reference to a tagset, if it exists.
+    pub fn tagset(&self, id: TagSetId) -> Option<&TagSet> {
+        self.tagsets.get(id.0 as usize).and_then(Option::as_ref)
+    }
+
+    /// Get a mutable reference to a tagset, if it exists.
+    fn tagset_mut(&mut self, id: TagSetId) -> Option<&mut TagSet> {
+        self.tagsets.get_mut(id.0 as usize).and_then(Option::as_mut)
+    }
+
+    /// Get a reference to the tagset shown on the focused screen.
+    pub fn focused_tagset(&self) -> &TagSet {
+        let id = self.screens[self.focused_screen.0 as usize].tagset;
+        // tagsets shown on a screen are never destroyed
+        self.tagset(id).unwrap()
+    }
+
+    /// Get a mutable reference to the tagset shown on the focused screen.
+    pub fn focused_tagset_mut(&mut self) -> &mut TagSet {
+        let id = self.screens[self.focused_screen.0 as usize].tagset;
+        self.tagset_mut(id).unwrap()
+    }
+
+    /// Check whether a tagset is shown on any screen.
+    pub fn is_visible(&self, id: TagSetId) -> bool {
+        self.screens.iter().any(|s| s.tagset == id)
+    }
+
+    /// Add a screen with the given geometry, showing a new tagset, and return it's id.
+    ///
+    /// The tagset shows the first tag not shown on any other screen yet, or a new tag named
+    /// after the screen if all tags known are shown already.
+    pub fn add_screen(&mut self, geometry: Geometry) -> ScreenId {
+        let id = ScreenId(self.screens.len() as u8);
+        let shown: HashSet<Tag> = self
+            .screens
+            .iter()
+            .filter_map(|s| self.tagset(s.tagset))
+            .flat_map(|t| t.tags.iter().cloned())
+            .collect();
+
+        let tag = match self.tags.all().into_iter().find(|t| !shown.contains(t)) {
+            Some(tag) => tag,
+            None => self.tags.intern(&format!("screen{}", id.0)),
+        };
+
+        let tagset = self.create_tagset(iter::once(tag).collect());
+        self.screens.push(Screen::new(geometry, tagset));
+
+        id
+    }
+
+    /// Create a new tagset with the given tags and the default layouts, and return it's id.
+    ///
+    /// The smallest id not in use by another tagset is allocated.
+    pub fn create_tagset(&mut self, tags: HashSet<Tag>) -> TagSetId {
+        let mut tagset = TagSet::new(tags, self.layouts.clone(), &self.clients);
+        tagset.gaps = self.gaps;
+
+        if let Some(index) = self.tagsets.iter().position(Option::is_none) {
+            self.tagsets[index] = Some(tagset);
+            TagSetId(index as u16)
+        } else {
+            self.tagsets.push(Some(tagset));
+            TagSetId(self.tagsets.len() as u16 - 1)
+        }
+    }
+
+    /// Get a tagset with the given tags and required tags, creating it if necessary.
+    ///
+    /// The tags are normalized first, so that equivalent tagsets are found. Tagsets created are
+    /// transient, and get destroyed once they are left.
+    pub fn find_or_create_tagset(&mut self, mut tags: HashSet<Tag>, mut required: HashSet<Tag>)
+        -> TagSetId
+    {
+        // a required tag also shown makes all other shown tags redundant
+        let redundant = tags.intersection(&required).min().cloned();
+        if let Some(tag) = redundant {
+            tags.clear();
+            tags.insert(tag);
+            required.remove(&tag);
+        }
+
+        let existing = self.tagsets.iter().position(|t| match *t {
+            Some(ref t) => t.tags == tags && t.required == required,
+            None => false,
+        });
+
+        if let Some(index) = existing {
+            return TagSetId(index as u16);
+        }
+
+        let id = self.create_tagset(tags);
+        let clients = &self.clients;
+        let tagset = self.tagsets[id.0 as usize].as_mut().unwrap();
+        tagset.required = required;
+        tagset.transient = true;
+        tagset.sync_tree(clients);
+
+        id
+    }
+
+    /// Destroy a tagset that has been left, if it is transient and not shown anymore.
+    fn drop_transient(&mut self, id: TagSetId) {
+        if self.tagset(id).map(|t| t.transient).unwrap_or(false) {
+            self.destroy_tagset(id);
+        }
+    }
+
+    /// Show the union of the focused screen's tagset and the given tags.
+    pub fn view_union(&mut self, tags: &HashSet<Tag>) -> bool {
+        let (current, required) = {
+            let tagset = self.focused_tagset();
+            (tagset.tags.union(tags).cloned().collect(), tagset.required.clone())
+        };
+
+        let id = self.find_or_create_tagset(current, required);
+        self.show_tagset(id)
+    }
+
+    /// Show the difference of the focused screen's tagset and the given tags.
+    ///
+    /// Removing all tags from the tagset is refused.
+    pub fn view_difference(&mut self, tags: &HashSet<Tag>) -> bool {
+        let (current, required): (HashSet<Tag>, HashSet<Tag>) = {
+            let tagset = self.focused_tagset();
+            (tagset.tags.difference(tags).cloned().collect(),
+             tagset.required.difference(tags).cloned().collect())
+        };
+
+        if current.is_empty() {
+            return false;
+        }
+
+        let id = self.find_or_create_tagset(current, required);
+        self.show_tagset(id)
+    }
+
+    /// Show the intersection of the focused screen's tagset and the given tags.
+    ///
+    /// Only clients shown on the focused screen that also carry all the given tags are shown.
+    pub fn view_intersection(&mut self, tags: &HashSet<Tag>) -> bool {
+        let (current, required) = {
+            let tagset = self.focused_tagset();
+            (tagset.tags.clone(), tagset.required.union(tags).cloned().collect())
+        };
+
+        let id = self.find_or_create_tagset(current, required);
+        self.show_tagset(id)
+    }
+
+    /// Destroy a tagset, freeing it's id for reuse.
+    ///
+    /// Tagsets currently shown on a screen can't be destroyed, which also guarantees that at
+    /// least one tagset is left. Returns `false` if the tagset could not be destroyed.
+    pub fn destroy_tagset(&mut self, id: TagSetId) -> bool {
+        if self.is_visible(id) || self.tagset(id).is_none() {
+            return false;
+        }
+
+        self.tagsets[id.0 as usize] = None;
+
+        for screen in &mut self.screens {
+            screen.history.retain(|&t| t != id);
+            screen.future.retain(|&t| t != id);
+        }
+
+        while let Some(&None) = self.tagsets.last() {
+            self.tagsets.pop();
+        }
+
+        true
+    }
+
+    /// Add tags to a tagset, inserting all clients that became visible into it's tree.
+    pub fn add_tags(&mut self, id: TagSetId, tags: HashSet<Tag>) -> bool {
+        let clients = &self.clients;
+
+        match self.tagsets.get_mut(id.0 as usize).and_then(Option::as_mut) {
+            Some(tagset) => {
+                tagset.tags.extend(tags);
+                tagset.sync_tree(clients);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Remove tags from a tagset, removing all clients that lost visibility from it's tree.
+    pub fn remove_tags(&mut self, id: TagSetId, tags: &HashSet<Tag>) -> bool {
+        let clients = &self.clients;
+
+        match self.tagsets.get_mut(id.0 as usize).and_then(Option::as_mut) {
+            Some(tagset) => {
+                tagset.tags.retain(|t| !tags.contains(t));
+                tagset.sync_tree(clients);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Change the layout of a tagset, making the tag tree layout-consistent.
+    ///
+    /// The layout replaces the tagset's layout of the same name, or is added to it's layouts.
+    pub fn set_layout(&mut self, id: TagSetId, layout: LayoutContainer) -> bool {
+        match self.tagset_mut(id) {
+            Some(tagset) => tagset.switch_layout(|l| {
+                l.replace(layout);
+                true
+            }),
+            None => false,
+        }
+    }
+
+    /// Switch to the layout following or preceding the current one on the focused tagset.
+    pub fn cycle_layout(&mut self, forward: bool) -> bool {
+        self.focused_tagset_mut().switch_layout(|l| l.cycle(forward))
+    }
+
+    /// Switch to the layout with the given name on the focused tagset, if it has one.
+    pub fn select_layout(&mut self, name: &str) -> bool {
+        self.focused_tagset_mut().switch_layout(|l| l.select(name))
+    }
+
+    /// Get the scratchpad client currently shown, if any.
+    pub fn scratchpad_client_shown(&self) -> Option<ClientId> {
+        self.scratchpad_shown.map(|(client, _)| client)
+    }
+
+    /// Stop waiting for a spawned scratchpad client, for example because the spawn failed.
+    pub fn cancel_scratchpad(&mut self) {
+        self.scratchpad_pending = None;
+    }
+
+    /// Show the scratchpad client on the focused screen, or hide it if it's shown already.
+    ///
+    /// The scratchpad client is the client carrying the scratchpad tag with the smallest id. If
+    /// no such client exists, an untagged client matching the scratchpad's class is moved to the
+    /// scratchpad, while clients tagged otherwise are left alone. If that fails as well, the
+    /// scratchpad command is to be spawned. Toggling the scratchpad while waiting for a spawned
+    /// client stops waiting for it.
+    pub fn toggle_scratchpad(&mut self) -> ScratchpadToggle {
+        // toggling again while waiting for a spawned client gives up on it
+        if self.scratchpad_pending.take().is_some() {
+            return ScratchpadToggle::Hidden;
+        }
+
+        if let Some((client, screen)) = self.scratchpad_shown.take() {
+            if screen == self.focused_screen {
+                return ScratchpadToggle::Hidden;
+            }
+
+            // shown on another screen, move it over instead
+            self.scratchpad_shown = Some((client, self.focused_screen));
+            return ScratchpadToggle::Shown;
+        }
+
+        let tagged = self
+            .clients
+            .values()
+            .filter(|c| c.tags.contains(&SCRATCHPAD))
+            .map(|c| c.window)
+            .min();
+
+        let client = tagged.or_else(|| {
+            let scratchpad = self.scratchpad.as_ref()?;
+            self.clients
+                .values()
+                .filter(|c| {
+                    c.tags.is_empty() && !c.iconic && scratchpad.matches(&c.properties.class)
+                })
+                .map(|c| c.window)
+                .min()
+        });
+
+        if let Some(client) = client {
+            let mut tags = HashSet::new();
+            tags.insert(SCRATCHPAD);
+            self.set_client_tags(client, tags);
+            self.scratchpad_shown = Some((client, self.focused_screen));
+
+            return ScratchpadToggle::Shown;
+        }
+
+        match self.scratchpad.as_ref().and_then(|s| s.command.clone()) {
+            Some(command) => {
+                self.scratchpad_pending = Some(Instant::now());
+                ScratchpadToggle::Spawn(command)
+            },
+            None => ScratchpadToggle::Missing,
+        }
+    }
+
+    /// Find the tagset and container carrying a mark.
+    pub fn find_mark(&self, mark: &str) -> Option<(TagSetId, ContainerId)> {
+        self.tagsets
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| {
+                t.as_ref().and_then(|t| t.tree.marked(mark)).map(|c| (TagSetId(i as u16), c))
+            })
+            .next()
+    }
+
+    /// Attach a mark to the focused container on the focused screen.
+    ///
+    /// The mark is removed from any other container carrying it, on any tagset.
+    pub fn set_mark(&mut self, mark: String) -> bool {
+        let focused = match self.focused_tagset().tree.focused {
+            Some(focused) => focused,
+            None => return false,
+        };
+
+        self.unset_mark(&mark);
+        self.focused_tagset_mut().tree.set_mark(mark, focused);
+
+        true
+    }
+
+    /// Remove a mark from the container carrying it, on any tagset.
+    ///
+    /// Snapshots of altered tag trees lose the mark as well, so that it stays unique once they
+    /// are restored.
+    pub fn unset_mark(&mut self, mark: &str) -> bool {
+        let mut found = false;
+
+        for tagset in self.tagsets.iter_mut().filter_map(Option::as_mut) {
+            found |= tagset.tree.unset_mark(mark);
+
+            if let Some(ref mut shadow) = tagset.shadow {
+                found |= shadow.unset_mark(mark);
+            }
+        }
+
+        found
+    }
+
+    /// Focus the container carrying a mark.
+    ///
+    /// If it's tagset is shown on a screen, that screen is focused. Otherwise, the tagset is
+    /// shown on the focused screen.
+    pub fn focus_mark(&mut self, mark: &str) -> bool {
+        let (tagset, id) = match self.find_mark(mark) {
+            Some(found) => found,
+            None => return false,
+        };
+
+        match self.screen_showing(tagset) {
+            Some(screen) => self.focused_screen = screen,
+            None => {
+                self.show_tagset(tagset);
+            },
+        }
+
+        self.tagset_mut(tagset).unwrap().tree.focus(id);
+
+        true
+    }
+
+    /// Swap the focused container on the focused screen with the container carrying a mark.
+    ///
+    /// If the containers live on different tagsets, they have to be leaves, and their clients
+    /// are exchanged in place instead.
+    pub fn swap_with_mark(&mut self, mark: &str) -> bool {
+        let (tagset, target) = match self.find_mark(mark) {
+            Some(found) => found,
+            None => return false,
+        };
+        let focused_tagset = self.screens[self.focused_screen.0 as usize].tagset;
+        let focused = match self.focused_tagset().tree.focused {
+            Some(focused) => focused,
+            None => return false,
+        };
+
+        if tagset == focused_tagset {
+            let tagset = self.focused_tagset_mut();
+
+            if tagset.tree.swap(focused, target) {
+                tagset.layout.as_layout().correct_tree(&mut tagset.tree);
+                true
+            } else {
+                false
+            }
+        } else {
+            self.exchange_clients((focused_tagset, focused), (tagset, target))
+        }
+    }
+
+    /// Exchange the clients of two leaf containers on different tagsets, in place.
+    ///
+    /// The marks of each container follow it's client, and each client trades the tags of the
+    /// tagset it leaves for the tags of the tagset it enters. Returns `false` if either container
+    /// is not a leaf, or if a client is already shown on the tagset it would enter.
+    fn exchange_clients(&mut self,
+                        (a_tagset, a): (TagSetId, ContainerId),
+                        (b_tagset, b): (TagSetId, ContainerId)) -> bool {
+        let client_of = |tagset: &TagSet, id| {
+            tagset.tree.get(id).and_then(Container::as_client).map(ClientContainer::client)
+        };
+        let (a_client, b_client) = {
+            let (ours, theirs) = (self.tagset(a_tagset).unwrap(), self.tagset(b_tagset).unwrap());
+
+            match (client_of(ours, a), client_of(theirs, b)) {
+                (Some(a_client), Some(b_client)) if ours.tree.find_client(b_client).is_none() &&
+                    theirs.tree.find_client(a_client).is_none() => (a_client, b_client),
+                _ => return false,
+            }
+        };
+
+        let a_marks = {
+            let tree = &mut self.tagset_mut(a_tagset).unwrap().tree;
+            tree.replace_client(a, b_client);
+            tree.take_marks(a)
+        };
+        let b_marks = {
+            let tree = &mut self.tagset_mut(b_tagset).unwrap().tree;
+            tree.replace_client(b, a_client);
+            let b_marks = tree.take_marks(b);
+
+            for mark in a_marks {
+                tree.set_mark(mark, b);
+            }
+
+            b_marks
+        };
+        {
+            let tree = &mut self.tagset_mut(a_tagset).unwrap().tree;
+
+            for mark in b_marks {
+                tree.set_mark(mark, a);
+            }
+        }
+
+        let moves = [(a_client, a_tagset, b_tagset), (b_client, b_tagset, a_tagset)];
+
+        for &(client, from, to) in &moves {
+            let tags = {
+                let (from, to) = (self.tagset(from).unwrap(), self.tagset(to).unwrap());
+                self.clients[&client]
+                    .tags
+                    .difference(&from.tags)
+                    .chain(&to.tags)
+                    .chain(&to.required)
+                    .cloned()
+                    .collect()
+            };
+
+            // the two trees involved hold the exchanged clients already, and are left alone
+            self.set_client_tags(client, tags);
+        }
+
+        // a client left might still be shown on it's old tagset, through other tags
+        for &id in &[a_tagset, b_tagset] {
+            let clients = &self.clients;
+            self.tagsets[id.0 as usize].as_mut().unwrap().sync_tree(clients);
+        }
+
+        true
+    }
+
+    /// Move the focused container on the focused screen next to the container carrying a mark.
+    ///
+    /// If the containers live on different tagsets, the clients in the focused container are
+    /// moved to the other tagset and placed next to the marked container, in order.
+    pub fn move_to_mark(&mut self, mark: &str) -> bool {
+        let (tagset, target) = match self.find_mark(mark) {
+            Some(found) => found,
+            None => return false,
+        };
+        let focused_tagset = self.screens[self.focused_screen.0 as usize].tagset;
+        let focused = match self.focused_tagset().tree.focused {
+            Some(focused) => focused,
+            None => return false,
+        };
+
+        if tagset == focused_tagset {
+            return self.focused_tagset_mut().move_next_to(focused, target);
+        }
+
+        let clients = self.focused_tagset().tree.subtree_clients(focused);
+        self.move_clients(&clients, tagset);
+
+        let tagset = self.tagset_mut(tagset).unwrap();
+        let mut previous = target;
+
+        for client in clients {
+            if let Some(id) = tagset.tree.find_client(client) {
+                if tagset.move_next_to(id, previous) {
+                    previous = id;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Replace the tags of the given clients, so that they are visible on a tagset.
+    fn move_clients(&mut self, clients: &[ClientId], id: TagSetId) {
+        let tags: HashSet<Tag> = match self.tagset(id) {
+            Some(tagset) => tagset.tags.union(&tagset.required).cloned().collect(),
+            None => return,
+        };
+
+        for &client in clients {
+            self.set_client_tags(client, tags.clone());
+        }
+    }
+
+    /// Get the hidden clients, in the order they have been hidden.
+    pub fn iconified(&self) -> &[ClientId] {
+        &self.iconified
+    }
+
+    /// Hide or restore a client, removing it from or inserting it into the trees of all tagsets
+    /// showing it.
+    fn set_iconic(&mut self, id: ClientId, iconic: bool) -> bool {
+        match self.clients.get_mut(&id) {
+            Some(client) if client.iconic != iconic => client.iconic = iconic,
+            _ => return false,
+        }
+
+        if iconic {
+            self.iconified.push(id);
+
+            if self.scratchpad_client_shown() == Some(id) {
+                self.scratchpad_shown = None;
+            }
+        } else {
+            self.iconified.retain(|&c| c != id);
+        }
+
+        let tags = &self.clients[&id].tags;
+
+        for tagset in self.tagsets.iter_mut().filter_map(Option::as_mut) {
+            if !tagset.shows_tags(tags) {
+                continue;
+            }
+
+            if !iconic {
+                tagset.insert_client(id);
+            } else if let Some(container) = tagset.tree.find_client(id) {
+                tagset.remove_client(container);
+            }
+
+            tagset.layout.as_layout().correct_tree(&mut tagset.tree);
+        }
+
+        true
+    }
+
+    /// Hide a client, keeping it's tags.
+    pub fn iconify(&mut self, id: ClientId) -> bool {
+        self.set_iconic(id, true)
+    }
+
+    /// Hide the client focused on the focused screen.
+    pub fn iconify_focused(&mut self) -> bool {
+        let client = {
+            let tree = &self.focused_tagset().tree;
+            tree.focused
+                .and_then(|f| tree.get(tree.focused_leaf(f)))
+                .and_then(Container::as_client)
+                .map(ClientContainer::client)
+        };
+
+        match client {
+            Some(client) => self.iconify(client),
+            None => false,
+        }
+    }
+
+    /// Restore a hidden client, or the client hidden last, and focus it if it is shown on the
+    /// focused screen.
+    pub fn restore(&mut self, id: Option<ClientId>) -> bool {
+        let id = match id.or_else(|| self.iconified.last().cloned()) {
+            Some(id) => id,
+            None => return false,
+        };
+
+        if !self.set_iconic(id, false) {
+            return false;
+        }
+
+        let tree = &mut self.focused_tagset_mut().tree;
+        if let Some(container) = tree.find_client(id) {
+            tree.focus(container);
+        }
+
+        true
+    }
+
+    /// Swap the focused container on the focused screen with the first container in the tree,
+    /// or with the second one if it is the first already.
+    pub fn zoom(&mut self) -> bool {
+        let tagset = self.focused_tagset_mut();
+        let focused = match tagset.tree.focused {
+            Some(focused) => focused,
+            None => return false,
+        };
+
+        let target = {
+            let children = &tagset.tree.get_split(tagset.tree.root).unwrap().children;
+            match children.iter().position(|&c| c == focused) {
+                Some(0) => children.get(1).cloned(),
+                _ => children.first().cloned(),
+            }
+        };
+
+        match target {
+            Some(target) if tagset.tree.swap(focused, target) => {
+                tagset.layout.as_layout().correct_tree(&mut tagset.tree);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Focus the container in the given direction of the focused container on the focused
+    /// screen, as determined by the layout.
+    pub fn focus_direction(&mut self, direction: Direction) -> bool {
+        let screen = &self.screens[self.focused_screen.0 as usize];
+        let tagset = self.tagsets[screen.tagset.0 as usize].as_mut().unwrap();
+        let target = tagset.tree.focused.and_then(|f| {
+            tagset.layout.as_layout().container_by_direction(&tagset.tree, screen, f, direction)
+        });
+
+        match target {
+            Some(target) => {
+                tagset.tree.focus(target);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Swap the focused container on the focused screen with the container in the given
+    /// direction, as determined by the layout.
+    pub fn swap_direction(&mut self, direction: Direction) -> bool {
+        let screen = &self.screens[self.focused_screen.0 as usize];
+        let tagset = self.tagsets[screen.tagset.0 as usize].as_mut().unwrap();
+        let (focused, target) = match tagset.tree.focused {
+            Some(focused) => {
+                let layout = tagset.layout.as_layout();
+                match layout.container_by_direction(&tagset.tree, screen, focused, direction) {
+                    Some(target) => (focused, target),
+                    None => return false,
+                }
+            },
+            None => return false,
+        };
+
+        if tagset.tree.swap(focused, target) {
+            tagset.layout.as_layout().correct_tree(&mut tagset.tree);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Select the focused container on the focused screen, or clear the selection.
+    pub fn toggle_selection(&mut self) -> bool {
+        self.focused_tagset_mut().tree.toggle_selection()
+    }
+
+    /// Get the screen currently focused.
+    pub fn focused_screen(&self) -> ScreenId {
+        self.focused_screen
+    }
+
+    /// Get the screen showing a tagset, if any.
+    pub fn screen_showing(&self, id: TagSetId) -> Option<ScreenId> {
+        self.screens.iter().position(|s| s.tagset == id).map(|i| ScreenId(i as u8))
+    }
+
+    /// Get the screen next to a screen in a given direction, if any.
+    ///
+    /// Geometric directions pick the closest screen whose center lies in that direction,
+    /// all other directions cycle through the screens in order.
+    pub fn screen_by_direction(&self, id: ScreenId, direction: Direction) -> Option<ScreenId> {
+        let n = self.screens.len();
+        let center = |g: &Geometry| {
+            (i64::from(g.x) + i64::from(g.width) / 2, i64::from(g.y) + i64::from(g.height) / 2)
+        };
+        let (x, y) = center(&self.screens.get(id.0 as usize)?.geometry);
+
+        match direction {
+            Direction::Left | Direction::Up | Direction::Right | Direction::Down => {
+                self.screens
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, s)| {
+                        let (sx, sy) = center(&s.geometry);
+                        let (dx, dy) = (sx - x, sy - y);
+                        let in_direction = match direction {
+                            Direction::Left => dx < 0 && dx.abs() >= dy.abs(),
+                            Direction::Right => dx > 0 && dx.abs() >= dy.abs(),
+                            Direction::Up => dy < 0 && dy.abs() >= dx.abs(),
+                            _ => dy > 0 && dy.abs() >= dx.abs(),
+                        };
+
+                        if in_direction { Some((dx * dx + dy * dy, i)) } else { None }
+                    })
+                    .min()
+                    .map(|(_, i)| ScreenId(i as u8))
+            },
+            Direction::InOrderBackward |
+            Direction::PreOrderBackward |
+            Direction::SiblingCycleBackward => Some(ScreenId(((id.0 as usize + n - 1) % n) as u8)),
+            _ => Some(ScreenId(((id.0 as usize + 1) % n) as u8)),
+        }
+    }
+
+    /// Focus a screen.
+    pub fn focus_screen(&mut self, id: ScreenId) -> bool {
+        if (id.0 as usize) < self.screens.len() && id != self.focused_screen {
+            self.focused_screen = id;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Replace the tagset shown on the focused screen, without touching it's history.
+    ///
+    /// If the tagset is already shown on another screen, that screen gets a new tagset
+    /// according to the view policy. Returns the tagset previously shown on the focused screen,
+    /// or `None` if nothing has changed.
+    fn view(&mut self, id: TagSetId) -> Option<TagSetId> {
+        self.tagset(id)?;
+
+        let current = self.screens[self.focused_screen.0 as usize].tagset;
+
+        if current == id {
+            return None;
+        }
+
+        if let Some(other) = self.screen_showing(id) {
+            let hidden = (0..self.tagsets.len() as u16)
+                .map(TagSetId)
+                .find(|&t| self.tagset(t).is_some() && !self.is_visible(t));
+
+            let replacement = match (self.view_policy, hidden) {
+                (ViewPolicy::Steal, Some(hidden)) => hidden,
+                _ => current,
+            };
+
+            self.screens[other.0 as usize].show(replacement);
+        }
+
+        self.screens[self.focused_screen.0 as usize].tagset = id;
+
+        Some(current)
+    }
+
+    /// Show a tagset on the focused screen.
+    ///
+    /// If the tagset is already shown on another screen, that screen gets a new tagset
+    /// according to the view policy.
+    pub fn show_tagset(&mut self, id: TagSetId) -> bool {
+        match self.view(id) {
+            Some(previous) => {
+                let screen = &mut self.screens[self.focused_screen.0 as usize];
+                screen.push_history(previous);
+                screen.future.clear();
+                self.drop_transient(previous);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Go back to the tagset shown previously on the focused screen.
+    pub fn history_back(&mut self) -> bool {
+        while let Some(id) = self.screens[self.focused_screen.0 as usize].history.pop_back() {
+            if let Some(previous) = self.view(id) {
+                self.screens[self.focused_screen.0 as usize].future.push(previous);
+                self.drop_transient(previous);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Go forward to the tagset left by going back on the focused screen.
+    pub fn history_forward(&mut self) -> bool {
+        while let Some(id) = self.screens[self.focused_screen.0 as usize].future.pop() {
+            if let Some(previous) = self.view(id) {
+                self.screens[self.focused_screen.0 as usize].push_history(previous);
+                self.drop_transient(previous);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Toggle between the current and the previously shown tagset on the focused screen.
+    pub fn history_toggle(&mut self) -> bool {
+        while let Some(id) = self.screens[self.focused_screen.0 as usize].history.pop_back() {
+            if let Some(previous) = self.view(id) {
+                let screen = &mut self.screens[self.focused_screen.0 as usize];
+                screen.push_history(previous);
+                screen.future.clear();
+                self.drop_transient(previous);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Swap the tagsets shown on two screens.
+    pub fn swap_screens(&mut self, a: ScreenId, b: ScreenId) -> bool {
+        let n = self.screens.len();
+
+        if a == b || a.0 as usize >= n || b.0 as usize >= n {
+            return false;
+        }
+
+        let tagset_a = self.screens[a.0 as usize].tagset;
+        let tagset_b = self.screens[b.0 as usize].tagset;
+        self.screens[a.0 as usize].show(tagset_b);
+        self.screens[b.0 as usize].show(tagset_a);
+
+        true
+    }
+
+    /// Move the focused screen's tagset to the screen in the given direction.
+    ///
+    /// The focused screen shows the other screen's tagset afterwards, and focus follows the
+    /// tagset moved.
+    pub fn move_tagset(&mut self, direction: Direction) -> bool {
+        let focused = self.focused_screen;
+
+        match self.screen_by_direction(focused, direction) {
+            Some(target) if self.swap_screens(focused, target) => {
+                self.focused_screen = target;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Focus the next or previous tab on the focused screen.
+    pub fn cycle_tab(&mut self, forward: bool) -> bool {
+        self.focused_tagset_mut().tree.cycle_tab(forward)
+    }
+
+    /// Split the focused container on the focused screen.
+    ///
+    /// Layouts that don't accept the resulting tree correct it again, and the message is
+    /// refused.
+    pub fn split(&mut self, split_type: SplitType) -> bool {
+        self.change_split(|tree, focused| tree.split(focused, split_type))
+    }
+
+    /// Toggle the split type of the focused container's parent on the focused screen.
+    pub fn toggle_split(&mut self) -> bool {
+        self.change_split(TagTree::toggle_split)
+    }
+
+    /// Change the split structure around the focused container on the focused screen.
+    fn change_split<F>(&mut self, f: F) -> bool
+        where F: FnOnce(&mut TagTree, ContainerId) -> bool
+    {
+        let tagset = self.focused_tagset_mut();
+        let focused = match tagset.tree.focused {
+            Some(focused) => focused,
+            None => tagset.tree.root,
+        };
+
+        if !f(&mut tagset.tree, focused) {
+            return false;
+        }
+
+        if tagset.layout.as_layout().check_tree(&tagset.tree) {
+            true
+        } else {
+            tagset.layout.as_layout().correct_tree(&mut tagset.tree);
+            false
+        }
+    }
+
+    /// Grow or shrink the focused container on the focused screen.
+    pub fn resize(&mut self, direction: Direction, amount: i32) -> bool {
+        self.focused_tagset_mut().tree.resize(direction, amount)
+    }
+
+    /// Reset the weights around the focused container on the focused screen, or in the whole
+    /// tree.
+    pub fn equalize(&mut self, all: bool) -> bool {
+        let tree = &mut self.focused_tagset_mut().tree;
+
+        if all {
+            let root = tree.root;
+            tree.equalize(root);
+            true
+        } else {
+            tree.equalize_focused()
+        }
+    }
+
+    /// Compute the geometries of all clients shown on any screen.
+    ///
+    /// Returns the geometries of all clients to be shown, the clients that are to be mapped
+    /// because they have become visible, and the clients that are to be unmapped because they
+    /// are no longer visible.
+    pub fn arrange(&mut self) -> (WindowSizes, Vec<ClientId>, Vec<ClientId>) {
+        let mut sizes = WindowSizes::new();
+
+        for screen in &self.screens {
+            let tagset = self.tagset(screen.tagset).unwrap();
+            let mut screen_sizes = WindowSizes::new();
+
+            tagset.layout.as_layout().compute_geo(&tagset.tree, screen, &mut screen_sizes);
+            tagset.gaps.apply(&screen.geometry, &mut screen_sizes);
+            sizes.extend(screen_sizes);
+        }
+
+        // the scratchpad floats centred above the tagset shown on it's screen
+        if let Some((client, screen)) = self.scratchpad_shown {
+            let screen = &self.screens[screen.0 as usize];
+            let (width, height) = self
+                .scratchpad
+                .as_ref()
+                .map(|s| (s.width, s.height))
+                .unwrap_or((DEFAULT_SCRATCHPAD_SIZE, DEFAULT_SCRATCHPAD_SIZE));
+
+            sizes.insert(client, screen.geometry.centred(width, height));
+        }
+
+        let mut mapped = Vec::new();
+        let mut unmapped = Vec::new();
+
+        for (id, client) in &mut self.clients {
+            if let Some(geometry) = sizes.get(id) {
+                client.geometry = *geometry;
+
+                if !client.mapped {
+                    client.mapped = true;
+                    mapped.push(*id);
+                }
+            } else if client.mapped {
+                client.mapped = false;
+                unmapped.push(*id);
+            }
+        }
+
+        (sizes, mapped, unmapped)
+    }
+
+    /// Get the window title of the client shown in a subtree of a tag tree.
+    fn subtree_name(&self, tree: &TagTree, id: ContainerId) -> &str {
+        tree.get(tree.focused_leaf(id))
+            .and_then(Container::as_client)
+            .and_then(|c| self.clients.get(&c.client))
+            .map(Client::name)
+            .unwrap_or("")
+    }
+
+    /// Get a snapshot of the arena, to be restored after a restart.
+    pub fn snapshot(&self) -> ArenaState {
+        let names = |tags: &HashSet<Tag>| -> Vec<String> {
+            self.tags.sorted(tags).into_iter().map(|t| self.tags.name(t).to_owned()).collect()
+        };
+
+        let mut clients: Vec<ClientState> = self.clients
+            .values()
+            .map(|c| ClientState {
+                window: c.window,
+                tags: names(&c.tags),
+                geometry: c.geometry,
+            })
+            .collect();
+        clients.sort_by_key(|c| c.window);
+
+        let tagsets = self.tagsets
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| t.as_ref().map(|t| (i, t)))
+            .map(|(i, t)| TagSetState {
+                id: i as u16,
+                tags: names(&t.tags),
+                required: names(&t.required),
+                layouts: t.layout.layouts().iter().map(|l| l.to_words().join(" ")).collect(),
+                layout: t.layout.index(),
+                gaps: t.gaps,
+                tree: t.tree.snapshot(t.tree.root, DEFAULT_WEIGHT),
+                shadow: t.shadow.clone(),
+                transient: t.transient,
+            })
+            .collect();
+
+        ArenaState {
+            clients,
+            iconified: self.iconified.clone(),
+            tagsets,
+            screens: self.screens.iter().map(|s| s.tagset.0).collect(),
+            focused_screen: self.focused_screen.0,
+        }
+    }
+
+    /// Restore a snapshot of the arena, adding the given clients.
+    ///
+    /// Clients present in the snapshot get their saved tags and geometry back, all others
+    /// keep the tags given. Tagsets keep their ids, and their trees are rebuilt from the saved
+    /// shape, dropping clients that don't exist anymore. Screens show their saved tagsets, but
+    /// never the same tagset twice, even if the number of screens has changed since.
+    /// Screens left without a saved tagset keep their own, or show the first hidden tagset.
+    pub fn load_snapshot(&mut self, state: ArenaState, clients: Vec<Client>) {
+        for mut client in clients {
+            if let Some(saved) = state.clients.iter().find(|c| c.window == client.window) {
+                client.tags = self.resolve_tags(&saved.tags);
+                client.geometry = saved.geometry;
+            }
+
+            client.iconic = state.iconified.contains(&client.window);
+            self.clients.insert(client.window, client);
+        }
+
+        self.iconified =
+            state.iconified.into_iter().filter(|c| self.clients.contains_key(c)).collect();
+
+        for saved in state.tagsets {
+            let layouts = saved
+                .layouts
+                .iter()
+                .filter_map(|l| {
+                    let words: Vec<&str> = l.split_whitespace().collect();
+                    LayoutContainer::parse_from_words(&words)
+                })
+                .collect();
+            let tagset = TagSet {
+                tags: self.resolve_tags(&saved.tags),
+                required: self.resolve_tags(&saved.required),
+                tree: TagTree::from_snapshot(saved.tree, &self.clients),
+                layout: LayoutList::with_current(layouts, saved.layout),
+                gaps: saved.gaps,
+                shadow: saved.shadow,
+                transient: saved.transient,
+            };
+
+            let index = saved.id as usize;
+            if self.tagsets.len() <= index {
+                self.tagsets.resize_with(index + 1, || None);
+            }
+            self.tagsets[index] = Some(tagset);
+        }
+
+        for tagset in self.tagsets.iter_mut().filter_map(Option::as_mut) {
+            tagset.sync_tree(&self.clients);
+        }
+
+        let mut shown = HashSet::new();
+
+        for i in 0..self.screens.len() {
+            let saved = state.screens.get(i).map(|&t| TagSetId(t));
+            let hidden = (0..self.tagsets.len() as u16).map(TagSetId);
+            let tagset = saved
+                .into_iter()
+                .chain(iter::once(self.screens[i].tagset))
+                .chain(hidden)
+                .find(|t| self.tagset(*t).is_some() && !shown.contains(t));
+
+            // there is at least one tagset per screen, so a free one is always found
+            if let Some(tagset) = tagset {
+                shown.insert(tagset);
+                self.screens[i].tagset = tagset;
+            }
+        }
+
+        if (state.focused_screen as usize) < self.screens.len() {
+            self.focused_screen = ScreenId(state.focused_screen);
+        }
+    }
+
+    /// Get the registry of all tags known.
+    pub fn tags(&self) -> &TagRegistry {
+        &self.tags
+    }
+
+    /// Write the status of the focused screen to the status stream.
+    ///
+    /// Each entry is written on a separate line, starting with the entry's name, followed by
+    /// tab-separated values.
+    pub fn write_status<W: Write>(&self, out: &mut W) -> IoResult<()> {
+        let focused = self.screens[self.focused_screen.0 as usize].tagset;
+
+        write!(out, "tags")?;
+        for tag in self.tags.all() {
+            let shown = self.focused_tagset().tags.contains(&tag);
+            let marker = if shown { "*" } else { "" };
+            write!(out, "\t{}{}", marker, self.tags.label(tag))?;
+        }
+        writeln!(out)?;
+
+        write!(out, "tagsets")?;
+        for (i, tagset) in self.tagsets.iter().enumerate() {
+            if let Some(ref tagset) = *tagset {
+                let marker = if TagSetId(i as u16) == focused { "*" } else { "" };
+                let labels = |tags| -> Vec<&str> {
+                    self.tags.sorted(tags).into_iter().map(|t| self.tags.label(t)).collect()
+                };
+
+                write!(out, "\t{}{}:{}", marker, i, labels(&tagset.tags).join(","))?;
+                if !tagset.required.is_empty() {
+                    write!(out, "&{}", labels(&tagset.required).join("&"))?;
+                }
+            }
+        }
+        writeln!(out)?;
+
+        let focused_container = self.focused_tagset().tree.focused;
+        let mut marks = BTreeMap::new();
+        for (i, tagset) in self.tagsets.iter().enumerate() {
+            if let Some(ref tagset) = *tagset {
+                for (mark, &id) in tagset.tree.marks() {
+                    let is_focused = TagSetId(i as u16) == focused && Some(id) == focused_container;
+                    marks.insert(mark.as_str(), is_focused);
+                }
+            }
+        }
+
+        write!(out, "marks")?;
+        for (mark, is_focused) in marks {
+            let marker = if is_focused { "*" } else { "" };
+            write!(out, "\t{}{}", marker, mark)?;
+        }
+        writeln!(out)?;
+
+        write!(out, "hidden")?;
+        for id in &self.iconified {
+            write!(out, "\t{:#x}:{}", id.0, self.clients[id].name())?;
+        }
+        writeln!(out)?;
+
+        let tree = &self.focused_tagset().tree;
+        let layout = self.focused_tagset().layout();
+
+        write!(out, "layout\t{}", layout.to_words().join(" "))?;
+        if let Some(status) = layout.as_layout().status(tree) {
+            write!(out, "\t{}", status)?;
+        }
+        writeln!(out)?;
+
+        // gwm-core doesn't draw any decorations, so the tab titles have to be shown by a bar.
+        write!(out, "tabs")?;
+        if let Some((tabbed, active)) = tree.focused_tabs() {
+            for (i, child) in tree.get_split(tabbed).unwrap().children.iter().enumerate() {
+                let marker = if i == active { "*" } else { "" };
+                write!(out, "\t{}{}", marker, self.subtree_name(tree, *child))?;
+            }
+        }
+        writeln!(out)?;
+
+        out.flush()
+    }
+
+    /// Write a dump of the tag tree shown on the focused screen.
+    ///
+    /// Each container is written on a separate line, indented by it's depth. The focused and
+    /// selected containers are marked with `*` and `+`, respectively, followed by the marks
+    /// they carry in brackets.
+    pub fn write_tree<W: Write>(&self, out: &mut W) -> IoResult<()> {
+        let tree = &self.focused_tagset().tree;
+        self.write_subtree(out, tree, tree.root, 0)
+    }
+
+    /// Write a dump of a subtree of a tag tree, indented by the given depth.
+    fn write_subtree<W: Write>(&self, out: &mut W, tree: &TagTree, id: ContainerId, depth: usize)
+        -> IoResult<()>
+    {
+        let container = match tree.get(id) {
+            Some(container) => container,
+            None => return Ok(()),
+        };
+
+        write!(out, "{:1$}", "", depth * 2)?;
+        match *container {
+            Container::Split(ref s) => write!(out, "{:?}", s.split_type)?,
+            Container::Client(ref c) => {
+                let name = self.clients.get(&c.client).map(Client::name).unwrap_or("");
+                write!(out, "{:#x} {:?}", c.client.0, name)?
+            },
+        }
+
+        if container.is_floating() {
+            write!(out, " floating")?;
+        }
+        if tree.focused == Some(id) {
+            write!(out, " *")?;
+        }
+        if tree.selected == Some(id) {
+            write!(out, " +")?;
+        }
+
+        let marks = tree.marks_of(id);
+        if !marks.is_empty() {
+            write!(out, " [{}]", marks.join(","))?;
+        }
+        writeln!(out)?;
+
+        if let Container::Split(ref s) = *container {
+            for &child in &s.children {
+                self.write_subtree(out, tree, child, depth + 1)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use wm::config::{arena_init, Config, ScratchpadConfig};
+    use wm::msg::Message;
+    use wm::testing::*;
+
+    fn weights(tree: &TagTree, id: ContainerId) -> Vec<u32> {
+        tree.get_split(id).unwrap().weights().to_vec()
+    }
+
+    fn windows(arena: &Arena, id: TagSetId) -> Vec<u32> {
+        let tree = arena.tagset(id).unwrap().tree();
+        tree.subtree_clients(tree.root()).into_iter().map(|c| c.0).collect()
+    }
+
+    fn tags(arena: &mut Arena, names: &[&str]) -> HashSet<Tag> {
+        let names: Vec<String> = names.iter().map(|&n| n.to_owned()).collect();
+        arena.resolve_tags(&names)
+    }
+
+    #[test]
+    fn cycle_tabs() {
+        let (mut tree, [a, _, c]) = nested_tree();
+        let root = tree.root();
+        assert_eq!(tree.focused_tabs(), None);
+        assert!(!tree.cycle_tab(true));
+
+        tree.split(root, SplitType::Tabbed);
+        let inner = tree.parent(c).unwrap();
+        assert_eq!(tree.focused_tabs(), Some((root, 1)));
+        assert!(tree.cycle_tab(true));
+        assert_eq!(tree.focused(), Some(a));
+        assert!(tree.cycle_tab(false));
+        assert_eq!(tree.focused(), Some(c));
+        assert_eq!(tree.get_split(root).unwrap().last_focused(), Some(inner));
+    }
+
+    #[test]
+    fn tab_status() {
+        let mut arena = arena(&["manual"]);
+        add_clients(&mut arena, &[1]);
+        assert_eq!(status(&arena, "tabs"), "");
+
+        assert!(accept(&mut arena, "generic split tabbed"));
+        add_clients(&mut arena, &[2, 3]);
+        assert_eq!(status(&arena, "tabs"), "c1\tc2\t*c3");
+        assert!(accept(&mut arena, "generic tab next"));
+        assert_eq!(status(&arena, "tabs"), "*c1\tc2\tc3");
+        assert!(accept(&mut arena, "generic tab prev"));
+        assert!(accept(&mut arena, "generic tab prev"));
+        assert_eq!(status(&arena, "tabs"), "c1\t*c2\tc3");
+
+        assert!(arena.set_client_name(ClientId(2), "editor".to_owned()));
+        assert_eq!(status(&arena, "tabs"), "c1\t*editor\tc3");
+    }
+
+    #[test]
+    fn split_weighted_is_proportional() {
+        assert_eq!(split_weighted(100, &[1, 3]), vec![(0, 25), (25, 75)]);
+        assert_eq!(split_weighted(100, &[1, 1, 1]), vec![(0, 33), (33, 33), (66, 34)]);
+        assert_eq!(split_weighted(90, &[0, 0]), vec![(0, 45), (45, 45)]);
+        assert!(split_weighted(90, &[]).is_empty());
+    }
+
+    #[test]
+    fn split_weighted_leaves_no_empty_parts() {
+        assert_eq!(split_weighted(120, &[1, 199]), vec![(0, 1), (1, 119)]);
+        assert_eq!(split_weighted(10, &[1, 1000, 1]), vec![(0, 1), (1, 8), (9, 1)]);
+        assert_eq!(split_weighted(120, &[55, 45]), vec![(0, 66), (66, 54)]);
+        assert_eq!(split_weighted(1, &[1, 1]), vec![(0, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn split_geometries() {
+        let geo = Geometry { x: 10, y: 20, width: 100, height: 50 };
+
+        assert_eq!(geo.split_horizontal(&[1, 1]),
+                   vec![Geometry { x: 10, y: 20, width: 50, height: 50 },
+                        Geometry { x: 60, y: 20, width: 50, height: 50 }]);
+        assert_eq!(geo.split_vertical(&[2, 3]),
+                   vec![Geometry { x: 10, y: 20, width: 100, height: 20 },
+                        Geometry { x: 10, y: 40, width: 100, height: 30 }]);
+    }
+
+    #[test]
+    fn insert_and_detach_keep_weights() {
+        let (mut tree, [a, b, c]) = nested_tree();
+        let root = tree.root();
+        let inner = tree.parent(b).unwrap();
+
+        assert_eq!(tree.get_split(root).unwrap().children(), &[a, inner]);
+        assert_eq!(tree.get_split(inner).unwrap().children(), &[b, c]);
+        assert_eq!(tree.get_split(inner).unwrap().split_type(), SplitType::Vertical);
+        assert_eq!(tree.leaves(root), vec![a, b, c]);
+
+        assert!(tree.resize(Direction::Left, 20));
+        tree.detach(a);
+        assert_eq!(tree.parent(a), None);
+        assert_eq!(tree.get_split(root).unwrap().children(), &[inner]);
+        assert_eq!(weights(&tree, root), vec![120]);
+
+        assert!(tree.attach_at(root, 0, a));
+        assert_eq!(tree.get_split(root).unwrap().children(), &[a, inner]);
+        assert_eq!(weights(&tree, root), vec![DEFAULT_WEIGHT, 120]);
+        assert!(!tree.append_child(a, b));
+    }
+
+    #[test]
+    fn flatten_resets_weights() {
+        let (mut tree, [a, b, c]) = nested_tree();
+        let (root, inner) = (tree.root(), tree.parent(b).unwrap());
+        assert!(tree.resize(Direction::Up, 30));
+        assert!(tree.resize(Direction::Left, 30));
+
+        assert!(!tree.is_flat());
+        tree.flatten();
+        assert!(tree.is_flat());
+        assert!(tree.get(inner).is_none());
+        assert_eq!(tree.get_split(root).unwrap().children(), &[a, b, c]);
+        assert_eq!(weights(&tree, root), vec![DEFAULT_WEIGHT; 3]);
+        assert_eq!(tree.focused(), Some(c));
+
+        tree.focus(a);
+        assert!(tree.resize(Direction::Right, 10));
+        tree.flatten();
+        assert_eq!(weights(&tree, root), vec![DEFAULT_WEIGHT; 3]);
+    }
+
+    #[test]
+    fn swap_keeps_weights_in_place() {
+        let (mut tree, [a, b, c]) = nested_tree();
+        let root = tree.root();
+        let inner = tree.parent(b).unwrap();
+        assert!(tree.resize(Direction::Left, 20));
+
+        assert!(tree.swap(a, c));
+        assert_eq!(tree.get_split(root).unwrap().children(), &[c, inner]);
+        assert_eq!(tree.get_split(inner).unwrap().children(), &[b, a]);
+        assert_eq!(weights(&tree, root), vec![80, 120]);
+
+        assert!(!tree.swap(b, inner));
+        assert!(!tree.swap(root, b));
+    }
+
+    #[test]
+    fn resize_uses_closest_split_along_direction() {
+        let (mut tree, [_, b, _]) = nested_tree();
+        let root = tree.root();
+        let inner = tree.parent(b).unwrap();
+
+        assert!(tree.resize(Direction::Left, 20));
+        assert_eq!(weights(&tree, root), vec![80, 120]);
+        assert_eq!(weights(&tree, inner), vec![DEFAULT_WEIGHT; 2]);
+
+        assert!(tree.resize(Direction::Up, 30));
+        assert_eq!(weights(&tree, inner), vec![70, 130]);
+
+        assert!(!tree.resize(Direction::Right, 10));
+        assert!(!tree.resize(Direction::Down, 10));
+
+        assert!(tree.resize(Direction::Left, -1000));
+        assert_eq!(weights(&tree, root), vec![199, 1]);
+        assert!(tree.resize(Direction::Left, 1000));
+        assert_eq!(weights(&tree, root), vec![1, 199]);
+
+        tree.equalize(root);
+        assert_eq!(weights(&tree, root), vec![DEFAULT_WEIGHT; 2]);
+        assert_eq!(weights(&tree, inner), vec![DEFAULT_WEIGHT; 2]);
+    }
+
+    #[test]
+    fn resize_messages() {
+        let mut arena = arena(&["manual"]);
+        add_clients(&mut arena, &[1, 2]);
+
+        assert!(accept(&mut arena, "generic grow up 50"));
+        assert!(!accept(&mut arena, "generic grow left 50"));
+        assert_eq!(arena.arrange().0[&ClientId(2)],
+                   Geometry { x: 0, y: 22, width: 120, height: 68 });
+
+        assert!(accept(&mut arena, "generic shrink up 1000"));
+        assert_eq!(arena.arrange().0[&ClientId(2)],
+                   Geometry { x: 0, y: 89, width: 120, height: 1 });
+
+        assert!(accept(&mut arena, "generic equalize"));
+        assert_eq!(arena.arrange().0[&ClientId(2)],
+                   Geometry { x: 0, y: 45, width: 120, height: 45 });
+    }
+
+    #[test]
+    fn create_edit_and_destroy_tagsets() {
+        let mut arena = arena(&["manual", "master"]);
+        assert!(accept(&mut arena, "generic tagset create a"));
+        assert!(accept(&mut arena, "generic tagset create b c"));
+        assert_eq!(status(&arena, "tagsets"), "*0:default\t1:a\t2:b,c");
+
+        assert!(!accept(&mut arena, "generic tagset destroy 0"));
+        assert!(accept(&mut arena, "generic tagset destroy 1"));
+        assert!(!accept(&mut arena, "generic tagset destroy 1"));
+        assert_eq!(status(&arena, "tagsets"), "*0:default\t2:b,c");
+        assert!(accept(&mut arena, "generic tagset create d"));
+        assert_eq!(status(&arena, "tagsets"), "*0:default\t1:d\t2:b,c");
+
+        assert!(accept(&mut arena, "generic tagset add 1 a"));
+        assert!(accept(&mut arena, "generic tagset remove 2 b"));
+        assert!(!accept(&mut arena, "generic tagset add 3 a"));
+        assert_eq!(status(&arena, "tagsets"), "*0:default\t1:a,d\t2:c");
+
+        assert!(accept(&mut arena, "generic tagset layout 1 master 60 2"));
+        assert_eq!(arena.tagset(TagSetId(1)).unwrap().layout().to_words(),
+                   ["master", "60", "2"]);
+
+        assert!(accept(&mut arena, "generic tagset destroy 2"));
+        assert_eq!(arena.tagsets.len(), 2);
+    }
+
+    #[test]
+    fn tagset_trees_follow_their_tags() {
+        let mut arena = arena(&["manual"]);
+        for &(window, tag) in &[(1, "a"), (2, "b"), (3, "a"), (4, "c")] {
+            let client = new_client(&mut arena, window, &[tag]);
+            arena.add_client(client);
+        }
+
+        let a = tags(&mut arena, &["a"]);
+        let id = arena.create_tagset(a.clone());
+        assert_eq!(windows(&arena, id), [1, 3]);
+
+        {
+            let tree = &mut arena.tagset_mut(id).unwrap().tree;
+            let (first, second) = (tree.find_client(ClientId(1)), tree.find_client(ClientId(3)));
+            assert!(tree.swap(first.unwrap(), second.unwrap()));
+        }
+
+        let b = tags(&mut arena, &["b"]);
+        assert!(arena.add_tags(id, b));
+        assert_eq!(windows(&arena, id), [3, 2, 1]);
+        assert!(arena.remove_tags(id, &a));
+        assert_eq!(windows(&arena, id), [2]);
+    }
+
+    #[test]
+    fn client_tags_keep_trees_in_sync() {
+        let mut arena = arena(&["manual"]);
+        add_clients(&mut arena, &[1, 2]);
+        let b = tags(&mut arena, &["b"]);
+        let other = arena.create_tagset(b.clone());
+
+        assert!(arena.set_client_tags(ClientId(1), b));
+        assert_eq!(windows(&arena, DEFAULT_TAGSET), [2]);
+        assert_eq!(windows(&arena, other), [1]);
+
+        let hidden = tags(&mut arena, &["b", "c"]);
+        assert!(!arena.set_client_tags(ClientId(1), hidden));
+        assert_eq!(windows(&arena, other), [1]);
+
+        assert!(arena.remove_client(ClientId(2)).is_some());
+        assert!(windows(&arena, DEFAULT_TAGSET).is_empty());
+        assert!(shown(&mut arena).is_empty());
+    }
+
+    #[test]
+    fn edit_client_tags() {
+        let mut arena = arena(&["manual"]);
+        add_clients(&mut arena, &[1, 2]);
+        assert!(accept(&mut arena, "generic tags add web"));
+        assert_eq!(shown(&mut arena), [1, 2]);
+        assert!(accept(&mut arena, "generic tags remove default"));
+        assert_eq!(shown(&mut arena), [1]);
+        let web = tags(&mut arena, &["web"]);
+        assert_eq!(arena.client(ClientId(2)).unwrap().tags, web);
+
+        assert!(accept(&mut arena, "generic select"));
+        assert!(accept(&mut arena, "generic tags set selected web"));
+        assert!(shown(&mut arena).is_empty());
+        assert!(accept(&mut arena, "generic view union web"));
+        assert_eq!(shown(&mut arena), [1, 2]);
+
+        for tags in &["selected", "selected selected", "web selected"] {
+            let msg = format!("generic tags add {}", tags);
+            let words: Vec<&str> = msg.split_whitespace().collect();
+            assert!(Message::parse_from_words(&words).is_none());
+        }
+    }
+
+    #[test]
+    fn scratchpad_tag_is_refused_in_messages() {
+        let mut arena = arena(&["manual"]);
+        add_clients(&mut arena, &[1]);
+
+        for msg in &["tags add scratchpad", "tagset create scratchpad", "view union scratchpad",
+                     "tagset add 0 default scratchpad"] {
+            let msg = format!("generic {}", msg);
+            let words: Vec<&str> = msg.split_whitespace().collect();
+            assert!(Message::parse_from_words(&words).is_none());
+        }
+
+        assert_eq!(shown(&mut arena), [1]);
+        assert_eq!(status(&arena, "tags"), "*default");
+    }
+
+    fn dual_head(view_policy: ViewPolicy) -> Arena {
+        let config = Config { layouts: vec![layout("manual")], view_policy, ..Config::default() };
+        arena_init(config, &[SCREEN, Geometry { x: 120, ..SCREEN }])
+    }
+
+    fn shown_tagsets(arena: &Arena) -> Vec<TagSetId> {
+        arena.screens.iter().map(Screen::tagset).collect()
+    }
+
+    #[test]
+    fn screens_show_tagsets() {
+        let mut arena = dual_head(ViewPolicy::Swap);
+        add_clients(&mut arena, &[1]);
+        let client = new_client(&mut arena, 2, &["screen1"]);
+        arena.add_client(client);
+        assert_eq!(shown_tagsets(&arena), [DEFAULT_TAGSET, TagSetId(1)]);
+
+        let (sizes, ..) = arena.arrange();
+        assert_eq!(sizes[&ClientId(1)].x, 0);
+        assert_eq!(sizes[&ClientId(2)].x, 120);
+
+        assert!(accept(&mut arena, "generic screen move right"));
+        assert_eq!(arena.focused_screen(), ScreenId(1));
+        assert_eq!(shown_tagsets(&arena), [TagSetId(1), DEFAULT_TAGSET]);
+        assert_eq!(arena.arrange().0[&ClientId(1)].x, 120);
+        assert!(!accept(&mut arena, "generic screen move right"));
+
+        assert!(accept(&mut arena, "generic screen focus left"));
+        assert_eq!(arena.focused_screen(), ScreenId(0));
+        assert!(!accept(&mut arena, "generic screen swap 0 2"));
+        assert!(accept(&mut arena, "generic screen swap 0 1"));
+        assert_eq!(shown_tagsets(&arena), [DEFAULT_TAGSET, TagSetId(1)]);
+
+        assert!(accept(&mut arena, "generic view 1"));
+        assert_eq!(shown_tagsets(&arena), [TagSetId(1), DEFAULT_TAGSET]);
+        assert!(!accept(&mut arena, "generic view 1"));
+    }
+
+    #[test]
+    fn steal_view_policy_shows_hidden_tagsets() {
+        let mut arena = dual_head(ViewPolicy::Steal);
+        assert!(accept(&mut arena, "generic view 1"));
+        assert_eq!(shown_tagsets(&arena), [TagSetId(1), DEFAULT_TAGSET]);
+
+        assert!(accept(&mut arena, "generic tagset create a"));
+        assert!(accept(&mut arena, "generic view 0"));
+        assert_eq!(shown_tagsets(&arena), [DEFAULT_TAGSET, TagSetId(2)]);
+    }
+
+    #[test]
+    fn history_back_forward_toggle() {
+        let mut arena = arena(&["manual"]);
+        let first = arena.screens[0].tagset();
+        let tags_a = tags(&mut arena, &["a"]);
+        let a = arena.create_tagset(tags_a);
+        let tags_b = tags(&mut arena, &["b"]);
+        let b = arena.create_tagset(tags_b);
+        let shown = |arena: &Arena| arena.screens[0].tagset();
+
+        assert!(!arena.history_back());
+        assert!(arena.show_tagset(a));
+        assert!(arena.show_tagset(b));
+
+        assert!(arena.history_back());
+        assert_eq!(shown(&arena), a);
+        assert!(arena.history_back());
+        assert_eq!(shown(&arena), first);
+        assert!(!arena.history_back());
+
+        assert!(arena.history_forward());
+        assert_eq!(shown(&arena), a);
+
+        assert!(arena.history_toggle());
+        assert_eq!(shown(&arena), first);
+        assert!(arena.history_toggle());
+        assert_eq!(shown(&arena), a);
+        assert!(!arena.history_forward());
+    }
+
+    #[test]
+    fn view_algebra() {
+        let mut arena = arena(&["manual"]);
+        for &(window, tags) in &[(1, &["default"][..]), (2, &["a"]), (3, &["default", "a"]),
+                                 (4, &["b"])] {
+            let client = new_client(&mut arena, window, tags);
+            arena.add_client(client);
+        }
+        assert_eq!(shown(&mut arena), [1, 3]);
+
+        assert!(accept(&mut arena, "generic view union a"));
+        assert_eq!(shown(&mut arena), [1, 2, 3]);
+        let union = arena.screens[0].tagset();
+        assert!(arena.tagset(union).unwrap().transient);
+
+        assert!(accept(&mut arena, "generic view intersection a"));
+        assert_eq!(shown(&mut arena), [2, 3]);
+        assert!(arena.tagset(union).is_none());
+
+        // the required tag `a` is shown as well, which makes `default` redundant
+        let intersection = arena.screens[0].tagset();
+        assert_eq!(status(&arena, "tags"), "default\t*a\tb");
+        assert!(!accept(&mut arena, "generic view difference a"));
+        assert!(accept(&mut arena, "generic view union default"));
+        assert!(arena.tagset(intersection).is_none());
+
+        assert!(accept(&mut arena, "generic view difference a"));
+        assert_eq!(arena.screens[0].tagset(), DEFAULT_TAGSET);
+
+        assert!(!accept(&mut arena, "generic view intersection default"));
+        assert!(!accept(&mut arena, "generic view difference default"));
+        assert_eq!(shown(&mut arena), [1, 3]);
+    }
+
+    fn with_scratchpad() -> Arena {
+        let scratchpad = ScratchpadConfig {
+            class: "term".to_owned(),
+            command: Some("xterm -class term".to_owned()),
+            width: 50,
+            height: 50,
+        };
+        let config = Config {
+            layouts: vec![layout("manual")],
+            scratchpad: Some(scratchpad),
+            ..Config::default()
+        };
+        arena_init(config, &[SCREEN])
+    }
+
+    fn add_terminal(arena: &mut Arena, window: u32) {
+        let mut client = new_client(arena, window, &["default"]);
+        client.properties.class = vec!["term".to_owned()];
+        arena.add_client(client);
+    }
+
+    fn spawns(toggle: ScratchpadToggle) -> Option<String> {
+        match toggle {
+            ScratchpadToggle::Spawn(command) => Some(command),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn toggle_scratchpad() {
+        let mut arena = with_scratchpad();
+        add_clients(&mut arena, &[1]);
+        add_terminal(&mut arena, 2);
+        assert_eq!(shown(&mut arena), [1]);
+
+        match arena.toggle_scratchpad() {
+            ScratchpadToggle::Shown => (),
+            _ => panic!("scratchpad not shown"),
+        }
+        assert_eq!(arena.scratchpad_client_shown(), Some(ClientId(2)));
+        assert_eq!(arena.arrange().0[&ClientId(2)],
+                   Geometry { x: 30, y: 22, width: 60, height: 45 });
+
+        match arena.toggle_scratchpad() {
+            ScratchpadToggle::Hidden => (),
+            _ => panic!("scratchpad not hidden"),
+        }
+        assert_eq!(shown(&mut arena), [1]);
+    }
+
+    #[test]
+    fn scratchpad_spawns_and_adopts_untagged_clients() {
+        let mut arena = with_scratchpad();
+        assert_eq!(spawns(arena.toggle_scratchpad()).unwrap(), "xterm -class term");
+        assert!(spawns(arena.toggle_scratchpad()).is_none());
+
+        assert!(spawns(arena.toggle_scratchpad()).is_some());
+        add_terminal(&mut arena, 1);
+        assert_eq!(arena.scratchpad_client_shown(), Some(ClientId(1)));
+        assert!(spawns(arena.toggle_scratchpad()).is_none());
+
+        // terminals tagged by the user stay where they are
+        let default = tags(&mut arena, &["default"]);
+        arena.set_client_tags(ClientId(1), default);
+        assert!(spawns(arena.toggle_scratchpad()).is_some());
+        arena.cancel_scratchpad();
+        assert_eq!(shown(&mut arena), [1]);
+
+        arena.set_client_tags(ClientId(1), HashSet::new());
+        assert!(spawns(arena.toggle_scratchpad()).is_none());
+        assert_eq!(arena.scratchpad_client_shown(), Some(ClientId(1)));
+    }
+
+    #[test]
+    fn marks_across_tagsets() {
+        let mut arena = arena(&["manual"]);
+        add_clients(&mut arena, &[1, 2]);
+        let client = new_client(&mut arena, 3, &["web"]);
+        arena.add_client(client);
+        let web = tags(&mut arena, &["web"]);
+        let other = arena.create_tagset(web.clone());
+
+        assert!(accept(&mut arena, "generic mark set a"));
+        assert!(accept(&mut arena, "generic view 1"));
+        assert!(accept(&mut arena, "generic mark set b"));
+        assert!(!accept(&mut arena, "generic mark focus c"));
+        assert!(accept(&mut arena, "generic mark focus a"));
+        assert_eq!(arena.screens[0].tagset(), DEFAULT_TAGSET);
+
+        assert!(accept(&mut arena, "generic mark swap b"));
+        assert_eq!(windows(&arena, DEFAULT_TAGSET), [1, 3]);
+        assert_eq!(windows(&arena, other), [2]);
+        assert_eq!(arena.client(ClientId(2)).unwrap().tags, web);
+        assert_eq!(arena.find_mark("a").map(|(tagset, _)| tagset), Some(other));
+
+        assert!(accept(&mut arena, "generic mark move a"));
+        assert_eq!(windows(&arena, DEFAULT_TAGSET), [1]);
+        assert_eq!(windows(&arena, other), [2, 3]);
+
+        assert!(accept(&mut arena, "generic mark unset a"));
+        assert!(!accept(&mut arena, "generic mark unset a"));
+    }
+
+    #[test]
+    fn move_to_marked_ancestor_keeps_clients() {
+        let mut arena = arena(&["manual"]);
+        add_clients(&mut arena, &[1, 2, 3]);
+
+        for msg in &["split horizontal", "focus pre_prev", "mark set w", "focus pre_next"] {
+            assert!(accept(&mut arena, &format!("generic {}", msg)));
+        }
+
+        assert!(!accept(&mut arena, "generic mark move w"));
+        assert_eq!(shown(&mut arena), [1, 2, 3]);
+    }
+
+    #[test]
+    fn iconify_and_restore() {
+        let mut arena = arena(&["manual"]);
+        add_clients(&mut arena, &[1, 2, 3]);
+        arena.arrange();
+        assert!(accept(&mut arena, "generic iconify"));
+        assert_eq!(arena.arrange().2, [ClientId(3)]);
+        assert_eq!(shown(&mut arena), [1, 2]);
+        assert_eq!(status(&arena, "hidden"), "0x3:c3");
+
+        // hidden clients keep their tags, but aren't shown anywhere
+        let web = tags(&mut arena, &["web", "default"]);
+        assert!(!arena.set_client_tags(ClientId(3), web.clone()));
+        assert_eq!(arena.client(ClientId(3)).unwrap().tags, web);
+
+        assert!(accept(&mut arena, "generic iconify"));
+        assert_eq!(arena.iconified(), [ClientId(3), ClientId(1)]);
+        assert!(accept(&mut arena, "generic restore 0x3"));
+        assert_eq!(shown(&mut arena), [2, 3]);
+        assert!(accept(&mut arena, "generic restore"));
+        assert!(!accept(&mut arena, "generic restore"));
+        assert_eq!(shown(&mut arena), [1, 2, 3]);
+    }
+
+    #[test]
+    fn arrange_reports_mapping_changes() {
+        let mut arena = arena(&["manual"]);
+        add_clients(&mut arena, &[1, 2]);
+        let (_, mut mapped, unmapped) = arena.arrange();
+        mapped.sort();
+        assert_eq!((mapped, unmapped), (vec![ClientId(1), ClientId(2)], vec![]));
+
+        let (sizes, mapped, unmapped) = arena.arrange();
+        assert_eq!(sizes.len(), 2);
+        assert!(mapped.is_empty() && unmapped.is_empty());
+
+        assert!(accept(&mut arena, "generic iconify"));
+        let (_, mapped, unmapped) = arena.arrange();
+        assert!(mapped.is_empty());
+        assert_eq!(unmapped, [ClientId(2)]);
+
+        assert!(accept(&mut arena, "generic restore"));
+        assert_eq!(arena.arrange().1, [ClientId(2)]);
+    }
+
+    fn reload(arena: &Arena, name: &str) -> ArenaState {
+        let path = env::temp_dir().join(format!("gwm-state-{}-{}.toml", process::id(), name));
+        assert!(arena.snapshot().save(&path).is_ok());
+        let state = ArenaState::load(&path).ok().unwrap();
+        fs::remove_file(&path).unwrap();
+        state
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let mut arena = arena(&["manual", "master"]);
+        add_clients(&mut arena, &[1, 2]);
+        assert!(accept(&mut arena, "generic split horizontal"));
+        add_clients(&mut arena, &[3]);
+        assert!(accept(&mut arena, "generic grow left 40"));
+        assert!(accept(&mut arena, "generic mark set m"));
+        assert!(accept(&mut arena, "generic view union b"));
+        let state = reload(&arena, "round-trip");
+
+        let mut restored = self::arena(&["manual", "master"]);
+        let clients = [3, 1, 2].iter().map(|&w| new_client(&mut restored, w, &["c"])).collect();
+        restored.load_snapshot(state, clients);
+
+        assert_eq!(dump(&restored), dump(&arena));
+        assert_eq!(restored.arrange().0, arena.arrange().0);
+        assert!(restored.focused_tagset().transient);
+        assert!(accept(&mut restored, "generic view 0"));
+        assert!(restored.tagset(TagSetId(1)).is_none());
+    }
+
+    #[test]
+    fn snapshot_shows_each_tagset_once() {
+        let mut arena = arena(&["manual"]);
+        assert!(accept(&mut arena, "generic tagset create a"));
+        assert!(accept(&mut arena, "generic view 1"));
+        let state = reload(&arena, "screens");
+
+        let mut restored = dual_head(ViewPolicy::Swap);
+        restored.load_snapshot(state, Vec::new());
+        assert_eq!(shown_tagsets(&restored), [TagSetId(1), DEFAULT_TAGSET]);
+    }
+
+    #[test]
+    fn gaps_apply() {
+        let mut sizes = WindowSizes::new();
+        sizes.insert(ClientId(1), Geometry { x: 0, y: 0, width: 60, height: 90 });
+        sizes.insert(ClientId(2), Geometry { x: 60, y: 0, width: 60, height: 90 });
+        sizes.insert(ClientId(3), Geometry { x: 0, y: 0, width: 8, height: 90 });
+        Gaps { inner: 9, outer: 4, smart: true }.apply(&SCREEN, &mut sizes);
+
+        assert_eq!(sizes[&ClientId(1)], Geometry { x: 4, y: 4, width: 51, height: 82 });
+        assert_eq!(sizes[&ClientId(2)], Geometry { x: 64, y: 4, width: 52, height: 82 });
+        assert_eq!(sizes[&ClientId(3)], Geometry { x: 0, y: 0, width: 8, height: 90 });
+
+        let mut sizes = WindowSizes::new();
+        sizes.insert(ClientId(1), SCREEN);
+        Gaps { inner: 9, outer: 4, smart: true }.apply(&SCREEN, &mut sizes);
+        assert_eq!(sizes[&ClientId(1)], SCREEN);
+        Gaps { inner: 9, outer: 4, smart: false }.apply(&SCREEN, &mut sizes);
+        assert_eq!(sizes[&ClientId(1)], Geometry { x: 4, y: 4, width: 112, height: 82 });
+    }
+
+    #[test]
+    fn gap_messages() {
+        let mut arena = arena(&["master"]);
+        add_clients(&mut arena, &[1]);
+
+        assert!(accept(&mut arena, "gaps inner abs 8"));
+        assert!(accept(&mut arena, "gaps outer inc 3"));
+        assert!(accept(&mut arena, "gaps outer dec 1"));
+        assert_eq!(arena.focused_tagset().gaps(), Gaps { inner: 8, outer: 2, smart: false });
+        assert_eq!(arena.arrange().0[&ClientId(1)],
+                   Geometry { x: 2, y: 2, width: 116, height: 86 });
+
+        assert!(accept(&mut arena, "gaps smart toggle"));
+        assert_eq!(arena.arrange().0[&ClientId(1)], SCREEN);
+    }
+
+    #[test]
+    fn layout_messages_keep_parameters() {
+        let mut arena = arena(&["manual", "master", "grid"]);
+        let current = |arena: &Arena| arena.focused_tagset().layout().to_words().join(" ");
+
+        assert!(accept(&mut arena, "generic layout next"));
+        assert_eq!(current(&arena), "master 55 1");
+        assert!(accept(&mut arena, "masterf abs 70"));
+        assert!(accept(&mut arena, "generic layout prev"));
+        assert_eq!(current(&arena), "manual");
+        assert!(accept(&mut arena, "generic layout prev"));
+        assert_eq!(current(&arena), "grid columns");
+        assert!(!accept(&mut arena, "generic layout monocle"));
+        assert!(accept(&mut arena, "generic layout master"));
+        assert_eq!(current(&arena), "master 70 1");
+    }
+
+    #[test]
+    fn shadow_restores_manual_tree() {
+        let mut arena = arena(&["manual", "master"]);
+        add_clients(&mut arena, &[1, 2]);
+        assert!(accept(&mut arena, "generic split horizontal"));
+        add_clients(&mut arena, &[3]);
+        assert!(accept(&mut arena, "generic grow up 30"));
+        let before = dump(&arena);
+        let sizes = arena.arrange().0;
+
+        assert!(accept(&mut arena, "generic layout master"));
+        assert!(arena.focused_tagset().tree().is_flat());
+        assert_ne!(dump(&arena), before);
+
+        assert!(accept(&mut arena, "generic layout manual"));
+        assert_eq!(dump(&arena), before);
+        assert_eq!(arena.arrange().0, sizes);
     }
 }
@@ -34,10 +34,13 @@
 
 /// Declare an enum hierarchy.
 ///
-/// Provides an enum enclosing the given types, and macros to match on it.
+/// Provides an enum enclosing the given types, and macros to match on it. Attributes given
+/// before the enum's name are attached to it.
 #[macro_export]
 macro_rules! declare_hierarchy {
-    ($enum_ident:ident; $macro_ident:ident $(, $name:ident)*) => {
+    ($(#[$attr:meta])* $enum_ident:ident; $macro_ident:ident $(, $name:ident)*) => {
+        $(#[$attr])*
+        #[derive(Debug)]
         pub enum $enum_ident {
             $($name($name)),*
         }
@@ -59,11 +62,15 @@ macro_rules! declare_hierarchy {
 /// parse enum variants from a simple word-based representation.
 #[macro_export]
 macro_rules! declare_hierarchy_with_parser {
-    ($enum_ident:ident; $macro_ident:ident $(, ($name: ident; $cmd:expr))*) => {
-        declare_hierarchy!($enum_ident; $macro_ident $(, $name)*);
+    ($(#[$attr:meta])* $enum_ident:ident; $macro_ident:ident $(, ($name: ident; $cmd:expr))*) => {
+        declare_hierarchy!($(#[$attr])* $enum_ident; $macro_ident $(, $name)*);
 
         impl $enum_ident {
             pub fn parse_from_words(words: &[&str]) -> Option<Self> {
+                if words.is_empty() {
+                    return None;
+                }
+
                 $(
                     if words[0] == $cmd {
                         return $name::parse_from_words(&words[1..]).map($enum_ident::$name);